@@ -0,0 +1,264 @@
+//! Decoding of escape sequences in character constants and string literals.
+//!
+//! The lexer hands us the raw text between the quotes, and we produce the bytes
+//! of the execution character set (UTF-8) that end up stored in the literal.
+//! Every problem is reported with a span covering exactly the offending escape,
+//! and decoding continues past it so that all bad escapes in a literal are
+//! reported at once.
+
+use std::fmt;
+
+use crate::span::Span;
+
+/// The largest value a single byte escape (`\x..` or octal) may take
+const MAX_BYTE_ESCAPE: u32 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeErrorKind {
+    /// A backslash followed by a character that does not begin any escape
+    Unknown(char),
+    /// A lone backslash at the very end of the literal
+    TrailingBackslash,
+    /// `\x` not followed by any hexadecimal digits
+    EmptyHex,
+    /// A hexadecimal escape whose value doesn't fit in a byte
+    HexOutOfRange,
+    /// An octal escape whose value doesn't fit in a byte
+    OctalOutOfRange,
+    /// `\u` or `\U` followed by fewer than the required number of hex digits
+    IncompleteUniversal { expected: usize },
+    /// A universal character name that C11 6.4.3 doesn't allow
+    InvalidUniversal(u32),
+}
+
+impl fmt::Display for EscapeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(c) => write!(f, "unknown escape sequence `\\{}`", c.escape_debug()),
+            Self::TrailingBackslash => write!(f, "incomplete escape sequence at end of literal"),
+            Self::EmptyHex => write!(f, "\\x used with no following hex digits"),
+            Self::HexOutOfRange => write!(f, "hex escape sequence out of range"),
+            Self::OctalOutOfRange => write!(f, "octal escape sequence out of range"),
+            Self::IncompleteUniversal { expected } => write!(
+                f,
+                "incomplete universal character name, expected {} hex digits",
+                expected
+            ),
+            Self::InvalidUniversal(value) => {
+                write!(f, "\\U{:08X} is not a valid universal character", value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscapeError {
+    pub kind: EscapeErrorKind,
+    /// The span of the escape sequence, including its leading backslash
+    pub span: Span,
+}
+
+/// Decodes the contents of a character constant or string literal.
+///
+/// `span` must cover exactly `contents`, so that the spans of any errors can be
+/// computed from byte offsets within it.
+pub fn decode(contents: &str, span: Span) -> Result<Vec<u8>, Vec<EscapeError>> {
+    debug_assert_eq!(contents.len(), span.len());
+
+    let bytes = contents.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut errors = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let (end, result) = decode_escape(contents, start);
+        i = end;
+
+        match result {
+            Ok(Decoded::Byte(b)) => out.push(b),
+            Ok(Decoded::Char(c)) => {
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            Err(kind) => errors.push(EscapeError {
+                kind,
+                span: span.subspan(start, end - start),
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(out)
+    } else {
+        Err(errors)
+    }
+}
+
+enum Decoded {
+    Byte(u8),
+    Char(char),
+}
+
+/// Decodes the escape beginning with the backslash at `start`, returning the
+/// offset just past the escape along with its value.
+fn decode_escape(contents: &str, start: usize) -> (usize, Result<Decoded, EscapeErrorKind>) {
+    let bytes = contents.as_bytes();
+    let first = start + 1;
+
+    let c = match contents[first..].chars().next() {
+        Some(c) => c,
+        None => return (first, Err(EscapeErrorKind::TrailingBackslash)),
+    };
+
+    let simple = match c {
+        '\'' => Some(b'\''),
+        '"' => Some(b'"'),
+        '?' => Some(b'?'),
+        '\\' => Some(b'\\'),
+        'a' => Some(0x07),
+        'b' => Some(0x08),
+        'f' => Some(0x0C),
+        'n' => Some(b'\n'),
+        'r' => Some(b'\r'),
+        't' => Some(b'\t'),
+        'v' => Some(0x0B),
+        _ => None,
+    };
+
+    if let Some(b) = simple {
+        return (first + 1, Ok(Decoded::Byte(b)));
+    }
+
+    match c {
+        '0'..='7' => {
+            let end = (first..bytes.len().min(first + 3))
+                .find(|&i| !matches!(bytes[i], b'0'..=b'7'))
+                .unwrap_or_else(|| bytes.len().min(first + 3));
+
+            // At most three octal digits, so this can't overflow
+            let value = u32::from_str_radix(&contents[first..end], 8).unwrap();
+
+            if value > MAX_BYTE_ESCAPE {
+                (end, Err(EscapeErrorKind::OctalOutOfRange))
+            } else {
+                (end, Ok(Decoded::Byte(value as u8)))
+            }
+        }
+        'x' => {
+            let digits = first + 1;
+            let end = (digits..bytes.len())
+                .find(|&i| !bytes[i].is_ascii_hexdigit())
+                .unwrap_or(bytes.len());
+
+            if end == digits {
+                return (end, Err(EscapeErrorKind::EmptyHex));
+            }
+
+            // Any number of digits is allowed, so saturate rather than overflow
+            let value = contents[digits..end].bytes().fold(0u32, |acc, d| {
+                acc.saturating_mul(16)
+                    .saturating_add((d as char).to_digit(16).unwrap())
+            });
+
+            if value > MAX_BYTE_ESCAPE {
+                (end, Err(EscapeErrorKind::HexOutOfRange))
+            } else {
+                (end, Ok(Decoded::Byte(value as u8)))
+            }
+        }
+        'u' | 'U' => {
+            let expected = if c == 'u' { 4 } else { 8 };
+            let digits = first + 1;
+            let end = (digits..bytes.len().min(digits + expected))
+                .find(|&i| !bytes[i].is_ascii_hexdigit())
+                .unwrap_or_else(|| bytes.len().min(digits + expected));
+
+            if end - digits != expected {
+                return (end, Err(EscapeErrorKind::IncompleteUniversal { expected }));
+            }
+
+            let value = u32::from_str_radix(&contents[digits..end], 16).unwrap();
+
+            (end, universal_char(value).map(Decoded::Char))
+        }
+        other => (
+            first + other.len_utf8(),
+            Err(EscapeErrorKind::Unknown(other)),
+        ),
+    }
+}
+
+/// Checks the constraints of C11 6.4.3 on a universal character name
+fn universal_char(value: u32) -> Result<char, EscapeErrorKind> {
+    let basic_exception = matches!(value, 0x24 | 0x40 | 0x60);
+
+    if value < 0xA0 && !basic_exception {
+        return Err(EscapeErrorKind::InvalidUniversal(value));
+    }
+
+    // This also rejects surrogates and anything past U+10FFFF
+    char::from_u32(value).ok_or(EscapeErrorKind::InvalidUniversal(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_str(s: &str) -> Result<Vec<u8>, Vec<EscapeError>> {
+        decode(s, Span::new(0, 10, 10 + s.len()))
+    }
+
+    #[test]
+    fn simple_escapes() {
+        assert_eq!(decode_str(r"a\n\t\\\'\?").unwrap(), b"a\n\t\\'?");
+        assert_eq!(decode_str(r"\a\b\f\v\r").unwrap(), [7, 8, 12, 11, 13]);
+    }
+
+    #[test]
+    fn numeric_escapes() {
+        assert_eq!(decode_str(r"\x41\101\0").unwrap(), b"AA\0");
+        assert_eq!(decode_str(r"\1234").unwrap(), b"S4");
+        assert_eq!(decode_str(r"\x00ff").unwrap(), [0xFF]);
+        assert_eq!(decode_str(r"\u00E9").unwrap(), "é".as_bytes());
+        assert_eq!(decode_str(r"\U0001F600").unwrap(), "😀".as_bytes());
+    }
+
+    #[test]
+    fn error_spans_cover_escape() {
+        let errors = decode_str(r"ok\x100 \q\777").unwrap_err();
+        let kinds: Vec<_> = errors.iter().map(|e| e.kind).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                EscapeErrorKind::HexOutOfRange,
+                EscapeErrorKind::Unknown('q'),
+                EscapeErrorKind::OctalOutOfRange,
+            ]
+        );
+        assert_eq!(errors[0].span, Span::new(0, 12, 17));
+        assert_eq!(errors[1].span, Span::new(0, 18, 20));
+        assert_eq!(errors[2].span, Span::new(0, 20, 24));
+    }
+
+    #[test]
+    fn malformed_escapes() {
+        let kind = |s| decode_str(s).unwrap_err()[0].kind;
+
+        assert_eq!(kind(r"\x"), EscapeErrorKind::EmptyHex);
+        assert_eq!(kind("abc\\"), EscapeErrorKind::TrailingBackslash);
+        assert_eq!(
+            kind(r"\u12"),
+            EscapeErrorKind::IncompleteUniversal { expected: 4 }
+        );
+        assert_eq!(kind(r"\u0041"), EscapeErrorKind::InvalidUniversal(0x41));
+        assert_eq!(kind(r"\uD800"), EscapeErrorKind::InvalidUniversal(0xD800));
+    }
+}
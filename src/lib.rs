@@ -1,3 +1,9 @@
+pub mod escape;
+pub mod sema;
+pub mod span;
+pub mod target;
+pub mod types;
+
 #[cfg(test)]
 mod tests {
     #[test]
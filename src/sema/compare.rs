@@ -0,0 +1,153 @@
+//! `-Wsign-compare`: relational and equality operators whose operands differ
+//! in signedness.
+//!
+//! The usual arithmetic conversions turn the signed operand unsigned whenever
+//! the unsigned one has at least its rank, so `-1 < 1u` is false. The warning
+//! labels both operands, since it is rarely obvious from the expression alone
+//! which of them is the one being converted.
+
+use std::fmt;
+
+use super::conversions::usual_arithmetic_conversions;
+use crate::span::Span;
+use crate::types::{Type, TypeContext, TypeKind};
+
+/// One operand of a comparison, with its constant value if it has one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub ty: Type,
+    pub span: Span,
+    pub value: Option<i128>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignCompare {
+    /// The operand with signed type, which gets converted
+    pub signed: Span,
+    pub signed_ty: String,
+    pub unsigned: Span,
+    pub unsigned_ty: String,
+    /// The unsigned type both operands are compared in
+    pub common_ty: String,
+}
+
+impl SignCompare {
+    /// The message to attach to each operand, signed operand first
+    pub fn labels(&self) -> [(Span, String); 2] {
+        [
+            (
+                self.signed,
+                format!(
+                    "this has type `{}` and is converted to `{}`",
+                    self.signed_ty, self.common_ty
+                ),
+            ),
+            (
+                self.unsigned,
+                format!("this has type `{}`", self.unsigned_ty),
+            ),
+        ]
+    }
+}
+
+impl fmt::Display for SignCompare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "comparison of integers of different signs: `{}` and `{}`",
+            self.signed_ty, self.unsigned_ty
+        )
+    }
+}
+
+/// Checks the operands of a comparison, both of which must already have been
+/// decayed.
+///
+/// Nothing is reported when the common type is signed, since every value then
+/// compares as written, nor when the signed operand is a constant that isn't
+/// negative, since converting it can't change it.
+pub fn check_sign_compare(
+    ctx: &mut TypeContext,
+    lhs: Operand,
+    rhs: Operand,
+) -> Option<SignCompare> {
+    if !ctx.is_integer(lhs.ty) || !ctx.is_integer(rhs.ty) {
+        return None;
+    }
+
+    let (signed, unsigned) = match (ctx.is_signed(lhs.ty), ctx.is_signed(rhs.ty)) {
+        (true, false) => (lhs, rhs),
+        (false, true) => (rhs, lhs),
+        _ => return None,
+    };
+
+    if matches!(signed.value, Some(value) if value >= 0) {
+        return None;
+    }
+
+    let common = usual_arithmetic_conversions(ctx, lhs.ty, rhs.ty);
+
+    match ctx.kind(common) {
+        TypeKind::Integer(kind) if !kind.is_signed(ctx.target()) => Some(SignCompare {
+            signed: signed.span,
+            signed_ty: ctx.display(signed.ty),
+            unsigned: unsigned.span,
+            unsigned_ty: ctx.display(unsigned.ty),
+            common_ty: ctx.display(common),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::TargetInfo;
+    use crate::types::IntKind;
+
+    fn operand(ctx: &mut TypeContext, kind: IntKind, lo: usize, value: Option<i128>) -> Operand {
+        Operand {
+            ty: ctx.int(kind),
+            span: Span::new(0, lo, lo + 1),
+            value,
+        }
+    }
+
+    #[test]
+    fn signed_converted_to_unsigned() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let uint = operand(&mut ctx, IntKind::UInt, 0, None);
+        let int = operand(&mut ctx, IntKind::Int, 4, None);
+
+        let warning = check_sign_compare(&mut ctx, uint, int).unwrap();
+
+        assert_eq!(warning.signed, int.span);
+        assert_eq!(
+            warning.to_string(),
+            "comparison of integers of different signs: `int` and `unsigned int`"
+        );
+        assert_eq!(
+            warning.labels()[0].1,
+            "this has type `int` and is converted to `unsigned int`"
+        );
+    }
+
+    #[test]
+    fn no_warning_when_values_are_preserved() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let uint = operand(&mut ctx, IntKind::UInt, 0, None);
+        let long = operand(&mut ctx, IntKind::Long, 4, None);
+        let ushort = operand(&mut ctx, IntKind::UShort, 8, None);
+        let int = operand(&mut ctx, IntKind::Int, 12, None);
+        let zero = operand(&mut ctx, IntKind::Int, 16, Some(0));
+        let minus_one = operand(&mut ctx, IntKind::Int, 16, Some(-1));
+
+        // The common type is signed
+        assert_eq!(check_sign_compare(&mut ctx, uint, long), None);
+        assert_eq!(check_sign_compare(&mut ctx, ushort, int), None);
+
+        // A non-negative constant survives the conversion
+        assert_eq!(check_sign_compare(&mut ctx, uint, zero), None);
+        assert!(check_sign_compare(&mut ctx, uint, minus_one).is_some());
+    }
+}
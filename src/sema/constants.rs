@@ -0,0 +1,422 @@
+//! Folding of integer constant expressions, and the warnings for constants
+//! that don't survive it intact.
+//!
+//! Values are carried as `i128` together with their C type and brought back
+//! into the range of that type after every operation, so that folding gives
+//! exactly what the target would compute at run time. Where the exact result
+//! and the wrapped one differ for a signed type, the program has undefined
+//! behaviour, which is worth a warning even though folding carries on with the
+//! wrapped value.
+
+use std::fmt;
+
+use super::conversions::{promote, usual_arithmetic_conversions};
+use crate::types::{IntKind, Type, TypeContext, TypeKind};
+
+/// An integer constant and the type it has in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntConstant {
+    pub value: i128,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    BitAnd,
+    BitOr,
+    BitXor,
+}
+
+/// Why an expression can't be folded to a constant at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstantError {
+    DivisionByZero,
+}
+
+impl fmt::Display for ConstantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero in constant expression"),
+        }
+    }
+}
+
+/// Something suspicious about a constant that could still be folded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstantWarning {
+    /// A signed operation whose exact result doesn't fit its type
+    Overflow {
+        result: i128,
+        ty: String,
+    },
+    /// A shift by at least the width of the promoted left operand
+    ShiftTooWide {
+        amount: i128,
+        ty: String,
+        width: u32,
+    },
+    NegativeShift(i128),
+    /// A left shift of a negative signed value (C11 6.5.7p4)
+    NegativeLeftShift(i128),
+    /// A constant converted to a type that can't represent it, like
+    /// `char c = 300;`
+    Truncation {
+        from: String,
+        to: String,
+        value: i128,
+        converted: i128,
+    },
+}
+
+impl fmt::Display for ConstantWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow { result, ty } => write!(
+                f,
+                "overflow in expression; result is {} with type `{}`",
+                result, ty
+            ),
+            Self::ShiftTooWide { amount, ty, width } => write!(
+                f,
+                "shift count {} is not less than the width of `{}` ({} bits)",
+                amount, ty, width
+            ),
+            Self::NegativeShift(amount) => write!(f, "shift count {} is negative", amount),
+            Self::NegativeLeftShift(value) => {
+                write!(f, "left shift of negative value {} is undefined", value)
+            }
+            Self::Truncation {
+                from,
+                to,
+                value,
+                converted,
+            } => write!(
+                f,
+                "implicit conversion from `{}` to `{}` changes value from {} to {}",
+                from, to, value, converted
+            ),
+        }
+    }
+}
+
+/// The result of folding one operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Folded {
+    pub constant: IntConstant,
+    pub warning: Option<ConstantWarning>,
+}
+
+fn int_kind(ctx: &TypeContext, ty: Type) -> IntKind {
+    match ctx.kind(ty) {
+        TypeKind::Integer(kind) => *kind,
+        _ => panic!("integer constant of type `{}`", ctx.display(ty)),
+    }
+}
+
+/// Brings the exact result of an operation into the range of `ty`, warning if
+/// that changed the value of a signed type
+fn finish(ctx: &TypeContext, exact: Option<i128>, wrapping: i128, ty: Type) -> Folded {
+    let kind = int_kind(ctx, ty);
+    let result = kind.wrap(wrapping, ctx.target());
+
+    let warning = if kind.is_signed(ctx.target()) && exact != Some(result) {
+        Some(ConstantWarning::Overflow {
+            result,
+            ty: ctx.display(ty),
+        })
+    } else {
+        None
+    };
+
+    Folded {
+        constant: IntConstant { value: result, ty },
+        warning,
+    }
+}
+
+pub fn fold_unary(ctx: &mut TypeContext, op: UnaryOp, operand: IntConstant) -> Folded {
+    let ty = promote(ctx, operand.ty);
+    let value = int_kind(ctx, ty).wrap(operand.value, ctx.target());
+
+    match op {
+        UnaryOp::Neg => finish(ctx, value.checked_neg(), value.wrapping_neg(), ty),
+        UnaryOp::BitNot => finish(ctx, Some(!value), !value, ty),
+    }
+}
+
+pub fn fold_binary(
+    ctx: &mut TypeContext,
+    op: BinaryOp,
+    lhs: IntConstant,
+    rhs: IntConstant,
+) -> Result<Folded, ConstantError> {
+    if let BinaryOp::Shl | BinaryOp::Shr = op {
+        return Ok(fold_shift(ctx, op, lhs, rhs));
+    }
+
+    let ty = usual_arithmetic_conversions(ctx, lhs.ty, rhs.ty);
+    let kind = int_kind(ctx, ty);
+    let a = kind.wrap(lhs.value, ctx.target());
+    let b = kind.wrap(rhs.value, ctx.target());
+
+    if b == 0 && matches!(op, BinaryOp::Div | BinaryOp::Rem) {
+        return Err(ConstantError::DivisionByZero);
+    }
+
+    // Even 64-bit operands can only overflow an i128 by multiplying, and the
+    // wrapped product still has the right low bits
+    let (exact, wrapping) = match op {
+        BinaryOp::Add => (a.checked_add(b), a.wrapping_add(b)),
+        BinaryOp::Sub => (a.checked_sub(b), a.wrapping_sub(b)),
+        BinaryOp::Mul => (a.checked_mul(b), a.wrapping_mul(b)),
+        BinaryOp::Div => (Some(a / b), a / b),
+        // `a % b` is undefined whenever `a / b` is, even though the remainder
+        // itself would be 0
+        BinaryOp::Rem if !kind.fits(a / b, ctx.target()) => (None, a % b),
+        BinaryOp::Rem => (Some(a % b), a % b),
+        BinaryOp::BitAnd => (Some(a & b), a & b),
+        BinaryOp::BitOr => (Some(a | b), a | b),
+        BinaryOp::BitXor => (Some(a ^ b), a ^ b),
+        BinaryOp::Shl | BinaryOp::Shr => unreachable!(),
+    };
+
+    Ok(finish(ctx, exact, wrapping, ty))
+}
+
+/// Shifts take the promoted type of their left operand alone (C11 6.5.7p3),
+/// and a count that is negative or not less than its width is undefined. Such
+/// a shift folds to zero. Left shifting a negative value is undefined as well,
+/// but is folded the way a two's complement machine would.
+fn fold_shift(ctx: &mut TypeContext, op: BinaryOp, lhs: IntConstant, rhs: IntConstant) -> Folded {
+    let ty = promote(ctx, lhs.ty);
+    let kind = int_kind(ctx, ty);
+    let width = kind.width(ctx.target());
+    let value = kind.wrap(lhs.value, ctx.target());
+
+    let rhs_ty = promote(ctx, rhs.ty);
+    let amount = int_kind(ctx, rhs_ty).wrap(rhs.value, ctx.target());
+
+    if amount < 0 || amount >= width as i128 {
+        let warning = if amount < 0 {
+            ConstantWarning::NegativeShift(amount)
+        } else {
+            ConstantWarning::ShiftTooWide {
+                amount,
+                ty: ctx.display(ty),
+                width,
+            }
+        };
+
+        return Folded {
+            constant: IntConstant { value: 0, ty },
+            warning: Some(warning),
+        };
+    }
+
+    match op {
+        // The shifted value can be far wider than the type, like an `unsigned
+        // long long` shifted by 63, and is only right once `finish` wraps it
+        // back to the low bits of the type, as with the multiply above
+        BinaryOp::Shl if value < 0 => {
+            let mut folded = finish(ctx, None, value << amount, ty);
+            folded.warning = Some(ConstantWarning::NegativeLeftShift(value));
+            folded
+        }
+        BinaryOp::Shl => finish(ctx, Some(value << amount), value << amount, ty),
+        BinaryOp::Shr => finish(ctx, Some(value >> amount), value >> amount, ty),
+        _ => unreachable!(),
+    }
+}
+
+/// Converts a constant to `to`, as happens to the initializer of an object of
+/// that type or the operand of a cast, warning if the value changes.
+///
+/// Explicit casts are written on purpose, so callers should only report the
+/// warning for implicit conversions. Converting to `_Bool` never warns since
+/// collapsing to 0 or 1 is the whole point of it.
+pub fn convert_constant(ctx: &TypeContext, constant: IntConstant, to: Type) -> Folded {
+    let kind = int_kind(ctx, to);
+    let converted = kind.wrap(constant.value, ctx.target());
+
+    let warning = if converted != constant.value && kind != IntKind::Bool {
+        Some(ConstantWarning::Truncation {
+            from: ctx.display(constant.ty),
+            to: ctx.display(to),
+            value: constant.value,
+            converted,
+        })
+    } else {
+        None
+    };
+
+    Folded {
+        constant: IntConstant {
+            value: converted,
+            ty: to,
+        },
+        warning,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::TargetInfo;
+
+    fn constant(ctx: &mut TypeContext, value: i128, kind: IntKind) -> IntConstant {
+        IntConstant {
+            value,
+            ty: ctx.int(kind),
+        }
+    }
+
+    #[test]
+    fn signed_overflow_warns() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let max = constant(&mut ctx, i32::MAX as i128, IntKind::Int);
+        let one = constant(&mut ctx, 1, IntKind::Int);
+
+        let folded = fold_binary(&mut ctx, BinaryOp::Add, max, one).unwrap();
+
+        assert_eq!(folded.constant.value, i32::MIN as i128);
+        assert_eq!(
+            folded.warning.unwrap().to_string(),
+            "overflow in expression; result is -2147483648 with type `int`"
+        );
+
+        let min = constant(&mut ctx, i32::MIN as i128, IntKind::Int);
+
+        assert!(fold_unary(&mut ctx, UnaryOp::Neg, min).warning.is_some());
+
+        let minus_one = constant(&mut ctx, -1, IntKind::Int);
+
+        assert!(fold_binary(&mut ctx, BinaryOp::Div, min, minus_one)
+            .unwrap()
+            .warning
+            .is_some());
+
+        let folded = fold_binary(&mut ctx, BinaryOp::Rem, min, minus_one).unwrap();
+
+        assert_eq!(folded.constant.value, 0);
+        assert!(folded.warning.is_some());
+
+        let big = constant(&mut ctx, i64::MAX as i128, IntKind::Long);
+        let folded = fold_binary(&mut ctx, BinaryOp::Mul, big, big).unwrap();
+
+        assert_eq!(folded.constant.value, 1);
+        assert!(folded.warning.is_some());
+    }
+
+    #[test]
+    fn unsigned_wraps_silently() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let zero = constant(&mut ctx, 0, IntKind::UInt);
+        let one = constant(&mut ctx, 1, IntKind::Int);
+
+        let folded = fold_binary(&mut ctx, BinaryOp::Sub, zero, one).unwrap();
+
+        assert_eq!(folded.constant.value, u32::MAX as i128);
+        assert_eq!(folded.warning, None);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let one = constant(&mut ctx, 1, IntKind::Int);
+        let zero = constant(&mut ctx, 0, IntKind::Int);
+
+        assert_eq!(
+            fold_binary(&mut ctx, BinaryOp::Rem, one, zero),
+            Err(ConstantError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn shifts() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let one = constant(&mut ctx, 1, IntKind::Char);
+        let forty = constant(&mut ctx, 40, IntKind::Int);
+        let minus = constant(&mut ctx, -1, IntKind::Int);
+        let four = constant(&mut ctx, 4, IntKind::ULongLong);
+
+        let folded = fold_binary(&mut ctx, BinaryOp::Shl, one, forty).unwrap();
+
+        assert_eq!(
+            folded.warning,
+            Some(ConstantWarning::ShiftTooWide {
+                amount: 40,
+                ty: String::from("int"),
+                width: 32
+            })
+        );
+        assert_eq!(
+            fold_binary(&mut ctx, BinaryOp::Shr, one, minus)
+                .unwrap()
+                .warning,
+            Some(ConstantWarning::NegativeShift(-1))
+        );
+
+        let folded = fold_binary(&mut ctx, BinaryOp::Shl, minus, one).unwrap();
+
+        assert_eq!(folded.constant.value, -2);
+        assert_eq!(
+            folded.warning.unwrap().to_string(),
+            "left shift of negative value -1 is undefined"
+        );
+
+        let max = constant(&mut ctx, u64::MAX as i128, IntKind::ULongLong);
+        let sixty_three = constant(&mut ctx, 63, IntKind::Int);
+        let folded = fold_binary(&mut ctx, BinaryOp::Shl, max, sixty_three).unwrap();
+
+        assert_eq!(folded.constant.value, 1 << 63);
+        assert_eq!(folded.warning, None);
+
+        // The count's type doesn't affect the result type
+        let folded = fold_binary(&mut ctx, BinaryOp::Shl, one, four).unwrap();
+
+        assert_eq!(folded.constant.value, 16);
+        assert_eq!(folded.constant.ty, ctx.int(IntKind::Int));
+        assert_eq!(folded.warning, None);
+    }
+
+    #[test]
+    fn truncating_initializer() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let value = constant(&mut ctx, 300, IntKind::Int);
+        let char = ctx.int(IntKind::Char);
+        let bool = ctx.int(IntKind::Bool);
+
+        let folded = convert_constant(&ctx, value, char);
+
+        assert_eq!(folded.constant.value, 44);
+        assert_eq!(
+            folded.warning.unwrap().to_string(),
+            "implicit conversion from `int` to `char` changes value from 300 to 44"
+        );
+        assert_eq!(convert_constant(&ctx, value, bool).warning, None);
+
+        // Plain char is unsigned on AArch64, so 200 fits there but not on x86_64
+        let value = constant(&mut ctx, 200, IntKind::Int);
+
+        assert!(convert_constant(&ctx, value, char).warning.is_some());
+
+        let mut ctx = TypeContext::new(TargetInfo::aarch64_linux());
+        let value = constant(&mut ctx, 200, IntKind::Int);
+        let char = ctx.int(IntKind::Char);
+
+        assert_eq!(convert_constant(&ctx, value, char).warning, None);
+    }
+}
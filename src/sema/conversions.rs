@@ -0,0 +1,651 @@
+//! Implicit conversions between types (C11 6.3) and the type rules for
+//! assignment (C11 6.5.16.1).
+//!
+//! These only look at types, so that the expression checker can decide which
+//! conversion node to insert and which error to report with whatever spans it
+//! has at hand.
+
+use std::fmt;
+
+use crate::types::{FloatKind, IntKind, Qualifiers, Type, TypeContext, TypeKind};
+
+/// An implicit conversion that has to be made explicit in the typed tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    /// Between two integer types of possibly different width or signedness
+    IntegralCast,
+    IntegralToFloating,
+    FloatingToIntegral,
+    /// Between two floating types of different precision
+    FloatingCast,
+    /// Comparison against zero when converting a scalar to `_Bool`
+    ToBoolean,
+    /// A null pointer constant becoming a pointer of some type
+    NullToPointer,
+    /// Between two pointer types, which doesn't change the representation
+    PointerCast,
+    ArrayToPointer,
+    FunctionToPointer,
+}
+
+/// Why a value of one type can't be assigned to an object of another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignError {
+    /// The types have nothing to do with each other, like a struct and an int
+    Incompatible,
+    /// Both are pointers, but to incompatible types
+    IncompatiblePointers,
+    /// The pointed-to type of the value has qualifiers the target's doesn't,
+    /// like assigning a `const char *` to a `char *`
+    DiscardsQualifiers(Qualifiers),
+    /// An integer other than a null pointer constant assigned to a pointer or
+    /// the other way around
+    IntPointerMismatch,
+}
+
+impl fmt::Display for AssignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incompatible => write!(f, "incompatible types"),
+            Self::IncompatiblePointers => write!(f, "incompatible pointer types"),
+            Self::DiscardsQualifiers(quals) => {
+                write!(
+                    f,
+                    "assignment discards `{}` qualifier from pointer target type",
+                    quals
+                )
+            }
+            Self::IntPointerMismatch => {
+                write!(f, "conversion between integer and pointer without a cast")
+            }
+        }
+    }
+}
+
+/// An implicit conversion that can change the value being converted, which
+/// `-Wconversion` warns about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Narrowing {
+    /// To a narrower integer type
+    Integer,
+    /// Between a signed and an unsigned integer type that is at least as wide,
+    /// which changes the meaning of the sign bit but loses nothing else
+    Signedness,
+    FloatingToIntegral,
+    /// From an integer type wider than the precision of the floating type
+    IntegralToFloating,
+    /// To a floating type with less range and precision
+    Floating,
+}
+
+impl fmt::Display for Narrowing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer => write!(f, "implicit conversion loses integer precision"),
+            Self::Signedness => write!(f, "implicit conversion changes signedness"),
+            Self::FloatingToIntegral => {
+                write!(
+                    f,
+                    "implicit conversion turns floating-point number into integer"
+                )
+            }
+            Self::IntegralToFloating => {
+                write!(f, "implicit conversion from integer may lose precision")
+            }
+            Self::Floating => write!(f, "implicit conversion loses floating-point precision"),
+        }
+    }
+}
+
+/// Which kinds of [`Narrowing`] to warn about, so that for example sign
+/// changes can be silenced while truncation is still reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NarrowingWarnings {
+    pub integer: bool,
+    pub signedness: bool,
+    pub floating_to_integral: bool,
+    pub integral_to_floating: bool,
+    pub floating: bool,
+}
+
+impl NarrowingWarnings {
+    pub const ALL: Self = Self {
+        integer: true,
+        signedness: true,
+        floating_to_integral: true,
+        integral_to_floating: true,
+        floating: true,
+    };
+
+    pub fn is_enabled(&self, narrowing: Narrowing) -> bool {
+        match narrowing {
+            Narrowing::Integer => self.integer,
+            Narrowing::Signedness => self.signedness,
+            Narrowing::FloatingToIntegral => self.floating_to_integral,
+            Narrowing::IntegralToFloating => self.integral_to_floating,
+            Narrowing::Floating => self.floating,
+        }
+    }
+}
+
+impl Default for NarrowingWarnings {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Converts arrays and functions to pointers, as happens to the value of any
+/// expression that isn't the operand of `sizeof` or `&` (C11 6.3.2.1).
+///
+/// Qualifiers on an array type really belong to its elements (C11 6.7.3p9),
+/// which is how an array member of a `const` struct ends up qualified, so they
+/// move onto the pointed-to type.
+pub fn decay(ctx: &mut TypeContext, ty: Type) -> (Type, Option<Conversion>) {
+    match ctx.kind(ty).clone() {
+        TypeKind::Array { element, .. } => (
+            ctx.pointer_to(element.with_quals(ty.quals)),
+            Some(Conversion::ArrayToPointer),
+        ),
+        TypeKind::Function { .. } => (ctx.pointer_to(ty), Some(Conversion::FunctionToPointer)),
+        _ => (ty.unqualified(), None),
+    }
+}
+
+/// Applies the integer promotions (C11 6.3.1.1p2) to an integer type, leaving
+/// any other type unchanged
+pub fn promote(ctx: &mut TypeContext, ty: Type) -> Type {
+    let kind = match ctx.kind(ty) {
+        TypeKind::Integer(kind) => *kind,
+        _ => return ty.unqualified(),
+    };
+
+    if kind.rank() >= IntKind::Int.rank() {
+        return ty.unqualified();
+    }
+
+    let target = ctx.target();
+    let fits_in_int = kind.is_signed(target) || kind.width(target) < IntKind::Int.width(target);
+
+    if fits_in_int {
+        ctx.int(IntKind::Int)
+    } else {
+        ctx.int(IntKind::UInt)
+    }
+}
+
+/// Finds the common type of the operands of a binary arithmetic operator using
+/// the usual arithmetic conversions (C11 6.3.1.8). Both types must be
+/// arithmetic.
+pub fn usual_arithmetic_conversions(ctx: &mut TypeContext, a: Type, b: Type) -> Type {
+    debug_assert!(ctx.is_arithmetic(a) && ctx.is_arithmetic(b));
+
+    for kind in [FloatKind::LongDouble, FloatKind::Double, FloatKind::Float] {
+        let floating = TypeKind::Floating(kind);
+
+        if *ctx.kind(a) == floating || *ctx.kind(b) == floating {
+            return ctx.float(kind);
+        }
+    }
+
+    let a = promote(ctx, a);
+    let b = promote(ctx, b);
+
+    let (a_kind, b_kind) = match (ctx.kind(a), ctx.kind(b)) {
+        (TypeKind::Integer(a), TypeKind::Integer(b)) => (*a, *b),
+        _ => unreachable!(),
+    };
+
+    if a_kind == b_kind {
+        return a;
+    }
+
+    let target = ctx.target();
+    let a_signed = a_kind.is_signed(target);
+    let b_signed = b_kind.is_signed(target);
+
+    if a_signed == b_signed {
+        return if a_kind.rank() >= b_kind.rank() { a } else { b };
+    }
+
+    let (signed, unsigned) = if a_signed {
+        (a_kind, b_kind)
+    } else {
+        (b_kind, a_kind)
+    };
+
+    let result = if unsigned.rank() >= signed.rank() {
+        unsigned
+    } else if signed.width(target) > unsigned.width(target) {
+        // The signed type can represent every value of the unsigned one
+        signed
+    } else {
+        signed.to_unsigned()
+    };
+
+    ctx.int(result)
+}
+
+/// Whether two types are compatible (C11 6.2.7), which is what makes two
+/// declarations of the same object agree and two pointer types interchangeable
+pub fn compatible(ctx: &TypeContext, a: Type, b: Type) -> bool {
+    if a == b {
+        return true;
+    }
+
+    if a.quals != b.quals {
+        return false;
+    }
+
+    match (ctx.kind(a), ctx.kind(b)) {
+        (TypeKind::Pointer(a), TypeKind::Pointer(b)) => compatible(ctx, *a, *b),
+        (
+            TypeKind::Array {
+                element: a_elem,
+                len: a_len,
+            },
+            TypeKind::Array {
+                element: b_elem,
+                len: b_len,
+            },
+        ) => {
+            let lens_agree = match (a_len, b_len) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            };
+
+            lens_agree && compatible(ctx, *a_elem, *b_elem)
+        }
+        (
+            TypeKind::Function {
+                ret: a_ret,
+                params: a_params,
+                variadic: a_variadic,
+                prototyped: a_prototyped,
+            },
+            TypeKind::Function {
+                ret: b_ret,
+                params: b_params,
+                variadic: b_variadic,
+                prototyped: b_prototyped,
+            },
+        ) => {
+            if !compatible(ctx, *a_ret, *b_ret) {
+                return false;
+            }
+
+            // An old-style declaration says nothing about the parameters, but
+            // calls through it pass arguments with only the default argument
+            // promotions applied, so a prototype mustn't expect anything else
+            // (C11 6.7.6.3p15)
+            if !a_prototyped || !b_prototyped {
+                let (params, variadic) = if *a_prototyped {
+                    (a_params, a_variadic)
+                } else {
+                    (b_params, b_variadic)
+                };
+
+                return !variadic && params.iter().all(|param| is_promoted(ctx, *param));
+            }
+
+            // Top-level qualifiers on parameters don't affect the function type
+            a_variadic == b_variadic
+                && a_params.len() == b_params.len()
+                && a_params
+                    .iter()
+                    .zip(b_params)
+                    .all(|(a, b)| compatible(ctx, a.unqualified(), b.unqualified()))
+        }
+        _ => false,
+    }
+}
+
+/// Whether a type is left unchanged by the default argument promotions
+/// (C11 6.5.2.2p6), which are the integer promotions plus `float` to `double`
+fn is_promoted(ctx: &TypeContext, ty: Type) -> bool {
+    match ctx.kind(ty) {
+        TypeKind::Integer(kind) => kind.rank() >= IntKind::Int.rank(),
+        TypeKind::Floating(kind) => *kind != FloatKind::Float,
+        _ => true,
+    }
+}
+
+/// Builds the composite of two compatible types (C11 6.2.7p3), which combines
+/// what each of them knows: an array length from one, a prototype from the
+/// other. This is the type an identifier has after being redeclared.
+pub fn composite(ctx: &mut TypeContext, a: Type, b: Type) -> Type {
+    debug_assert!(compatible(ctx, a, b));
+
+    if a == b {
+        return a;
+    }
+
+    let quals = a.quals;
+
+    let ty = match (ctx.kind(a).clone(), ctx.kind(b).clone()) {
+        (TypeKind::Pointer(a), TypeKind::Pointer(b)) => {
+            let pointee = composite(ctx, a, b);
+            ctx.pointer_to(pointee)
+        }
+        (
+            TypeKind::Array {
+                element: a_elem,
+                len: a_len,
+            },
+            TypeKind::Array {
+                element: b_elem,
+                len: b_len,
+            },
+        ) => {
+            let element = composite(ctx, a_elem, b_elem);
+            ctx.array_of(element, a_len.or(b_len))
+        }
+        (
+            TypeKind::Function {
+                ret: a_ret,
+                params: a_params,
+                variadic: a_variadic,
+                prototyped: a_prototyped,
+            },
+            TypeKind::Function {
+                ret: b_ret,
+                params: b_params,
+                variadic: b_variadic,
+                prototyped: b_prototyped,
+            },
+        ) => {
+            let ret = composite(ctx, a_ret, b_ret);
+
+            match (a_prototyped, b_prototyped) {
+                (true, true) => {
+                    let params = a_params
+                        .iter()
+                        .zip(&b_params)
+                        .map(|(a, b)| composite(ctx, a.unqualified(), b.unqualified()))
+                        .collect();
+
+                    ctx.function(ret, params, a_variadic)
+                }
+                (true, false) => ctx.function(ret, a_params, a_variadic),
+                (false, true) => ctx.function(ret, b_params, b_variadic),
+                (false, false) => ctx.intern(TypeKind::Function {
+                    ret,
+                    params: Vec::new(),
+                    variadic: false,
+                    prototyped: false,
+                }),
+            }
+        }
+        _ => a,
+    };
+
+    ty.with_quals(quals)
+}
+
+/// Checks that a value of type `from` may be assigned to an object of type
+/// `to` (C11 6.5.16.1), returning the conversion to apply if any.
+///
+/// `from` should already have been decayed. `is_null_constant` says whether the
+/// value is a null pointer constant, which only the caller can tell since it
+/// depends on the expression rather than its type.
+pub fn assignment_conversion(
+    ctx: &TypeContext,
+    from: Type,
+    to: Type,
+    is_null_constant: bool,
+) -> Result<Option<Conversion>, AssignError> {
+    let from = from.unqualified();
+    let to = to.unqualified();
+
+    match (ctx.kind(from), ctx.kind(to)) {
+        (TypeKind::Integer(a), TypeKind::Integer(b)) if a == b => Ok(None),
+        (TypeKind::Floating(a), TypeKind::Floating(b)) if a == b => Ok(None),
+        (
+            TypeKind::Integer(_) | TypeKind::Floating(_) | TypeKind::Pointer(_),
+            TypeKind::Integer(IntKind::Bool),
+        ) => Ok(Some(Conversion::ToBoolean)),
+        (TypeKind::Integer(_), TypeKind::Integer(_)) => Ok(Some(Conversion::IntegralCast)),
+        (TypeKind::Integer(_), TypeKind::Floating(_)) => Ok(Some(Conversion::IntegralToFloating)),
+        (TypeKind::Floating(_), TypeKind::Integer(_)) => Ok(Some(Conversion::FloatingToIntegral)),
+        (TypeKind::Floating(_), TypeKind::Floating(_)) => Ok(Some(Conversion::FloatingCast)),
+        (TypeKind::Integer(_), TypeKind::Pointer(_)) if is_null_constant => {
+            Ok(Some(Conversion::NullToPointer))
+        }
+        (TypeKind::Integer(_), TypeKind::Pointer(_))
+        | (TypeKind::Pointer(_), TypeKind::Integer(_)) => Err(AssignError::IntPointerMismatch),
+        (TypeKind::Pointer(from_pointee), TypeKind::Pointer(to_pointee)) => {
+            let (from_pointee, to_pointee) = (*from_pointee, *to_pointee);
+
+            // The pointed-to type of the target must have every qualifier of
+            // the value's, or the assignment would silently drop them
+            if !to_pointee.quals.contains(from_pointee.quals) {
+                let missing = Qualifiers {
+                    is_const: from_pointee.quals.is_const && !to_pointee.quals.is_const,
+                    is_volatile: from_pointee.quals.is_volatile && !to_pointee.quals.is_volatile,
+                    is_restrict: from_pointee.quals.is_restrict && !to_pointee.quals.is_restrict,
+                };
+
+                return Err(AssignError::DiscardsQualifiers(missing));
+            }
+
+            let either_void = ctx.is_void(from_pointee) || ctx.is_void(to_pointee);
+
+            if either_void || compatible(ctx, from_pointee.unqualified(), to_pointee.unqualified())
+            {
+                if from == to {
+                    Ok(None)
+                } else {
+                    Ok(Some(Conversion::PointerCast))
+                }
+            } else {
+                Err(AssignError::IncompatiblePointers)
+            }
+        }
+        (TypeKind::Record(..), TypeKind::Record(..)) if from == to => Ok(None),
+        _ => Err(AssignError::Incompatible),
+    }
+}
+
+/// Checks whether implicitly converting a value of arithmetic type `from` to
+/// arithmetic type `to` can change it.
+///
+/// This only looks at the types, so a constant that happens to fit should be
+/// checked with [`convert_constant`](super::constants::convert_constant)
+/// instead. Converting to `_Bool` is never narrowing, since collapsing to 0 or
+/// 1 is what it's for.
+pub fn narrowing(ctx: &TypeContext, from: Type, to: Type) -> Option<Narrowing> {
+    let target = ctx.target();
+
+    match (ctx.kind(from), ctx.kind(to)) {
+        (_, TypeKind::Integer(IntKind::Bool)) => None,
+        (TypeKind::Integer(from), TypeKind::Integer(to)) => {
+            let (from_min, from_max) = from.range(target);
+
+            if to.fits(from_min, target) && to.fits(from_max, target) {
+                None
+            } else if to.width(target) < from.width(target) {
+                Some(Narrowing::Integer)
+            } else {
+                Some(Narrowing::Signedness)
+            }
+        }
+        (TypeKind::Floating(_), TypeKind::Integer(_)) => Some(Narrowing::FloatingToIntegral),
+        (TypeKind::Integer(from), TypeKind::Floating(to)) => {
+            // The sign bit doesn't need a significand bit
+            let bits = from.width(target) - from.is_signed(target) as u32;
+
+            if bits > to.precision(target) {
+                Some(Narrowing::IntegralToFloating)
+            } else {
+                None
+            }
+        }
+        (TypeKind::Floating(from), TypeKind::Floating(to)) => {
+            if to.precision(target) < from.precision(target) {
+                Some(Narrowing::Floating)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::TargetInfo;
+    use crate::types::RecordKind;
+
+    #[test]
+    fn arithmetic_conversions() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let char = ctx.int(IntKind::Char);
+        let ushort = ctx.int(IntKind::UShort);
+        let int = ctx.int(IntKind::Int);
+        let uint = ctx.int(IntKind::UInt);
+        let long = ctx.int(IntKind::Long);
+        let ulong = ctx.int(IntKind::ULong);
+        let double = ctx.float(FloatKind::Double);
+
+        assert_eq!(promote(&mut ctx, char), int);
+        assert_eq!(promote(&mut ctx, ushort), int);
+        assert_eq!(usual_arithmetic_conversions(&mut ctx, char, ushort), int);
+        assert_eq!(usual_arithmetic_conversions(&mut ctx, int, uint), uint);
+        assert_eq!(usual_arithmetic_conversions(&mut ctx, uint, long), long);
+        assert_eq!(usual_arithmetic_conversions(&mut ctx, long, ulong), ulong);
+        assert_eq!(
+            usual_arithmetic_conversions(&mut ctx, ulong, double),
+            double
+        );
+    }
+
+    #[test]
+    fn assignment() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let int = ctx.int(IntKind::Int);
+        let char = ctx.int(IntKind::Char);
+        let void = ctx.void();
+        let char_ptr = ctx.pointer_to(char);
+        let const_char_ptr = ctx.pointer_to(char.with_quals(Qualifiers::CONST));
+        let int_ptr = ctx.pointer_to(int);
+        let void_ptr = ctx.pointer_to(void);
+        let (record, _) = ctx.new_record(RecordKind::Struct, Some("s"));
+
+        assert_eq!(
+            assignment_conversion(&ctx, int, char, false),
+            Ok(Some(Conversion::IntegralCast))
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, char_ptr, const_char_ptr, false),
+            Ok(Some(Conversion::PointerCast))
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, int_ptr, void_ptr, false),
+            Ok(Some(Conversion::PointerCast))
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, int, int_ptr, true),
+            Ok(Some(Conversion::NullToPointer))
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, record, int, false),
+            Err(AssignError::Incompatible)
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, int_ptr, char_ptr, false),
+            Err(AssignError::IncompatiblePointers)
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, int, int_ptr, false),
+            Err(AssignError::IntPointerMismatch)
+        );
+        assert_eq!(
+            assignment_conversion(&ctx, const_char_ptr, char_ptr, false),
+            Err(AssignError::DiscardsQualifiers(Qualifiers::CONST))
+        );
+    }
+
+    #[test]
+    fn narrowing_conversions() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let bool = ctx.int(IntKind::Bool);
+        let char = ctx.int(IntKind::Char);
+        let uchar = ctx.int(IntKind::UChar);
+        let int = ctx.int(IntKind::Int);
+        let uint = ctx.int(IntKind::UInt);
+        let ulong = ctx.int(IntKind::ULong);
+        let long = ctx.int(IntKind::Long);
+        let float = ctx.float(FloatKind::Float);
+        let double = ctx.float(FloatKind::Double);
+        let long_double = ctx.float(FloatKind::LongDouble);
+
+        assert_eq!(narrowing(&ctx, char, int), None);
+        assert_eq!(narrowing(&ctx, uchar, int), None);
+        assert_eq!(narrowing(&ctx, long, bool), None);
+        assert_eq!(narrowing(&ctx, long, int), Some(Narrowing::Integer));
+        assert_eq!(narrowing(&ctx, uint, int), Some(Narrowing::Signedness));
+        assert_eq!(narrowing(&ctx, char, uint), Some(Narrowing::Signedness));
+        assert_eq!(narrowing(&ctx, int, ulong), Some(Narrowing::Signedness));
+        assert_eq!(narrowing(&ctx, long, uint), Some(Narrowing::Integer));
+        assert_eq!(
+            narrowing(&ctx, double, long),
+            Some(Narrowing::FloatingToIntegral)
+        );
+        assert_eq!(narrowing(&ctx, int, double), None);
+        assert_eq!(
+            narrowing(&ctx, int, float),
+            Some(Narrowing::IntegralToFloating)
+        );
+        assert_eq!(
+            narrowing(&ctx, long, double),
+            Some(Narrowing::IntegralToFloating)
+        );
+        assert_eq!(narrowing(&ctx, long, long_double), None);
+        assert_eq!(narrowing(&ctx, double, float), Some(Narrowing::Floating));
+        assert_eq!(narrowing(&ctx, float, double), None);
+
+        let warnings = NarrowingWarnings {
+            signedness: false,
+            ..NarrowingWarnings::ALL
+        };
+
+        assert!(!warnings.is_enabled(Narrowing::Signedness));
+        assert!(warnings.is_enabled(Narrowing::Integer));
+    }
+
+    #[test]
+    fn function_compatibility() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let int = ctx.int(IntKind::Int);
+        let const_int = int.with_quals(Qualifiers::CONST);
+        let a = ctx.function(int, vec![int], false);
+        let b = ctx.function(int, vec![const_int], false);
+        let c = ctx.function(int, vec![int, int], false);
+        let old_style = ctx.intern(TypeKind::Function {
+            ret: int,
+            params: vec![],
+            variadic: false,
+            prototyped: false,
+        });
+
+        assert!(compatible(&ctx, a, b));
+        assert!(!compatible(&ctx, a, c));
+        assert!(compatible(&ctx, a, old_style));
+
+        // Arguments to an old-style function are only promoted, so they can
+        // never match a narrower parameter or a variable argument list
+        let char = ctx.int(IntKind::Char);
+        let float = ctx.float(FloatKind::Float);
+        let takes_char = ctx.function(int, vec![char], false);
+        let takes_float = ctx.function(int, vec![float], false);
+        let variadic = ctx.function(int, vec![int], true);
+
+        assert!(!compatible(&ctx, old_style, takes_char));
+        assert!(!compatible(&ctx, takes_float, old_style));
+        assert!(!compatible(&ctx, old_style, variadic));
+    }
+}
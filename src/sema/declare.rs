@@ -0,0 +1,331 @@
+//! Merging redeclarations of an identifier into its existing symbol.
+//!
+//! C allows declaring the same object or function many times as long as the
+//! declarations agree, and an object at file scope may have any number of
+//! tentative definitions (C11 6.9.2) that collapse into a single definition.
+//! What it doesn't allow is two definitions, declarations with incompatible
+//! types, or reusing a name for a different kind of entity in the same scope.
+
+use std::fmt;
+
+use super::conversions::{compatible, composite};
+use super::linkage::Linkage;
+use super::scope::Symbol;
+use crate::span::Span;
+use crate::types::{Type, TypeContext};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Object,
+    Function,
+    Typedef,
+    EnumConstant,
+}
+
+impl fmt::Display for EntityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Object => "variable",
+            Self::Function => "function",
+            Self::Typedef => "typedef",
+            Self::EnumConstant => "enumerator",
+        })
+    }
+}
+
+/// How far along an identifier is towards being defined. The ordering matters:
+/// merging two declarations keeps the greater of the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Definition {
+    /// Only declared, like `extern int x;` or a function prototype
+    Declared,
+    /// A file scope object declared without an initializer and without
+    /// `extern`, which becomes a zero-initialized definition at the end of the
+    /// translation unit unless a real definition shows up
+    Tentative,
+    /// Initialized objects, function bodies, and everything at block scope
+    /// without linkage
+    Defined,
+}
+
+/// What the symbol table knows about an ordinary identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub ty: Type,
+    pub linkage: Linkage,
+    pub definition: Definition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedeclarationErrorKind {
+    /// Defined twice, like two function bodies or two initializers
+    Redefinition,
+    /// Declared twice with incompatible types
+    ConflictingTypes,
+    /// Declared as one kind of entity and then another, like a variable and
+    /// then a typedef
+    DifferentKind { previous: EntityKind },
+}
+
+/// A redeclaration that can't be merged. The caller reports it at the new
+/// declaration, with a note pointing at `previous`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedeclarationError {
+    pub kind: RedeclarationErrorKind,
+    pub previous: Span,
+}
+
+impl RedeclarationError {
+    /// The text of the note attached at the earlier declaration
+    pub fn note(&self) -> &'static str {
+        match self.kind {
+            RedeclarationErrorKind::Redefinition => "previous definition is here",
+            _ => "previous declaration is here",
+        }
+    }
+}
+
+impl fmt::Display for RedeclarationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Redefinition => write!(f, "redefinition"),
+            Self::ConflictingTypes => write!(f, "conflicting types"),
+            Self::DifferentKind { previous } => {
+                write!(
+                    f,
+                    "redeclared as a different kind of symbol than the previous {}",
+                    previous
+                )
+            }
+        }
+    }
+}
+
+/// Merges a redeclaration in the same scope into the existing symbol, giving it
+/// the composite type of both declarations and upgrading a tentative definition
+/// if this is the real one.
+///
+/// Linkage conflicts are expected to have been diagnosed already, and `new`
+/// should carry the linkage that was determined for it.
+pub fn merge_declaration(
+    ctx: &mut TypeContext,
+    prior: &mut Symbol<Entity>,
+    new: Entity,
+    span: Span,
+) -> Result<(), RedeclarationError> {
+    let old = prior.data;
+    let error = |kind| {
+        Err(RedeclarationError {
+            kind,
+            previous: prior.span,
+        })
+    };
+
+    if old.kind != new.kind {
+        return error(RedeclarationErrorKind::DifferentKind { previous: old.kind });
+    }
+
+    match new.kind {
+        // A typedef may be repeated as long as it names the same type
+        EntityKind::Typedef if old.ty == new.ty => return Ok(()),
+        EntityKind::Typedef => return error(RedeclarationErrorKind::ConflictingTypes),
+        EntityKind::EnumConstant => return error(RedeclarationErrorKind::Redefinition),
+        EntityKind::Object | EntityKind::Function => {}
+    }
+
+    // Without linkage, every declaration is also a definition
+    if old.linkage == Linkage::None || new.linkage == Linkage::None {
+        return error(RedeclarationErrorKind::Redefinition);
+    }
+
+    if !compatible(ctx, old.ty, new.ty) {
+        return error(RedeclarationErrorKind::ConflictingTypes);
+    }
+
+    if old.definition == Definition::Defined && new.definition == Definition::Defined {
+        return error(RedeclarationErrorKind::Redefinition);
+    }
+
+    prior.data.ty = composite(ctx, old.ty, new.ty);
+
+    // Point later notes at the definition rather than some prototype
+    if new.definition > old.definition {
+        prior.data.definition = new.definition;
+        prior.span = span;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::TargetInfo;
+    use crate::types::{IntKind, TypeKind};
+
+    fn symbol(entity: Entity, lo: usize) -> Symbol<Entity> {
+        Symbol {
+            name: String::from("x"),
+            span: Span::new(0, lo, lo + 1),
+            data: entity,
+        }
+    }
+
+    fn object(ty: Type, definition: Definition) -> Entity {
+        Entity {
+            kind: EntityKind::Object,
+            ty,
+            linkage: Linkage::External,
+            definition,
+        }
+    }
+
+    fn function(ty: Type, definition: Definition) -> Entity {
+        Entity {
+            kind: EntityKind::Function,
+            ..object(ty, definition)
+        }
+    }
+
+    #[test]
+    fn tentative_definitions_merge() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let unknown_len = ctx.array_of(int, None);
+        let sized = ctx.array_of(int, Some(3));
+
+        // int x[]; int x[3]; int x[] = { 1, 2, 3 };
+        let mut prior = symbol(object(unknown_len, Definition::Tentative), 0);
+        let second = Span::new(0, 10, 11);
+        let third = Span::new(0, 20, 21);
+
+        merge_declaration(
+            &mut ctx,
+            &mut prior,
+            object(sized, Definition::Tentative),
+            second,
+        )
+        .unwrap();
+        merge_declaration(
+            &mut ctx,
+            &mut prior,
+            object(unknown_len, Definition::Defined),
+            third,
+        )
+        .unwrap();
+
+        assert_eq!(prior.data.ty, sized);
+        assert_eq!(prior.data.definition, Definition::Defined);
+        assert_eq!(prior.span, third);
+
+        let err = merge_declaration(
+            &mut ctx,
+            &mut prior,
+            object(sized, Definition::Defined),
+            second,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind, RedeclarationErrorKind::Redefinition);
+        assert_eq!(err.previous, third);
+    }
+
+    #[test]
+    fn prototype_completes_old_style_declaration() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let old_style = ctx.intern(TypeKind::Function {
+            ret: int,
+            params: vec![],
+            variadic: false,
+            prototyped: false,
+        });
+        let prototype = ctx.function(int, vec![int], false);
+
+        let mut prior = symbol(function(old_style, Definition::Declared), 0);
+        let span = Span::new(0, 5, 6);
+
+        merge_declaration(
+            &mut ctx,
+            &mut prior,
+            function(prototype, Definition::Defined),
+            span,
+        )
+        .unwrap();
+
+        assert_eq!(prior.data.ty, prototype);
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let long = ctx.int(IntKind::Long);
+        let span = Span::new(0, 5, 6);
+
+        let mut prior = symbol(object(int, Definition::Declared), 0);
+
+        let conflicting = merge_declaration(
+            &mut ctx,
+            &mut prior,
+            object(long, Definition::Declared),
+            span,
+        );
+        assert_eq!(
+            conflicting.unwrap_err().kind,
+            RedeclarationErrorKind::ConflictingTypes
+        );
+
+        let typedef = Entity {
+            kind: EntityKind::Typedef,
+            ..object(int, Definition::Defined)
+        };
+        let different = merge_declaration(&mut ctx, &mut prior, typedef, span);
+        assert_eq!(
+            different.unwrap_err().kind,
+            RedeclarationErrorKind::DifferentKind {
+                previous: EntityKind::Object
+            }
+        );
+
+        // int f(); int f(char c) {...} and int f(); int f(int, ...);
+        let old_style = ctx.intern(TypeKind::Function {
+            ret: int,
+            params: vec![],
+            variadic: false,
+            prototyped: false,
+        });
+        let char = ctx.int(IntKind::Char);
+        let takes_char = ctx.function(int, vec![char], false);
+        let variadic = ctx.function(int, vec![int], true);
+
+        for (prototype, definition) in [
+            (takes_char, Definition::Defined),
+            (variadic, Definition::Declared),
+        ] {
+            let mut prior = symbol(function(old_style, Definition::Declared), 0);
+            let conflicting =
+                merge_declaration(&mut ctx, &mut prior, function(prototype, definition), span);
+
+            assert_eq!(
+                conflicting.unwrap_err().kind,
+                RedeclarationErrorKind::ConflictingTypes
+            );
+        }
+
+        let local = Entity {
+            linkage: Linkage::None,
+            ..object(int, Definition::Defined)
+        };
+        let mut prior = symbol(local, 0);
+        let redefined = merge_declaration(&mut ctx, &mut prior, local, span);
+        assert_eq!(
+            redefined.unwrap_err().kind,
+            RedeclarationErrorKind::Redefinition
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Calls to functions that haven't been declared.
+//!
+//! C89 implicitly declared such functions as `extern int f()`, which silently
+//! miscompiles anything that doesn't return `int`. C99 removed the rule, so it
+//! is an error unless the legacy behaviour is asked for, and either way we try
+//! to point the user at the header they most likely forgot.
+
+use crate::types::{IntKind, Type, TypeContext, TypeKind};
+
+/// Common standard library functions and the header declaring them, sorted by
+/// name so they can be binary searched
+const KNOWN_FUNCTIONS: &[(&str, &str)] = &[
+    ("abort", "stdlib.h"),
+    ("abs", "stdlib.h"),
+    ("assert", "assert.h"),
+    ("atof", "stdlib.h"),
+    ("atoi", "stdlib.h"),
+    ("atol", "stdlib.h"),
+    ("calloc", "stdlib.h"),
+    ("ceil", "math.h"),
+    ("clock", "time.h"),
+    ("cos", "math.h"),
+    ("exit", "stdlib.h"),
+    ("exp", "math.h"),
+    ("fabs", "math.h"),
+    ("fclose", "stdio.h"),
+    ("fflush", "stdio.h"),
+    ("fgetc", "stdio.h"),
+    ("fgets", "stdio.h"),
+    ("floor", "math.h"),
+    ("fopen", "stdio.h"),
+    ("fprintf", "stdio.h"),
+    ("fputc", "stdio.h"),
+    ("fputs", "stdio.h"),
+    ("fread", "stdio.h"),
+    ("free", "stdlib.h"),
+    ("fscanf", "stdio.h"),
+    ("fseek", "stdio.h"),
+    ("ftell", "stdio.h"),
+    ("fwrite", "stdio.h"),
+    ("getc", "stdio.h"),
+    ("getchar", "stdio.h"),
+    ("getenv", "stdlib.h"),
+    ("isalnum", "ctype.h"),
+    ("isalpha", "ctype.h"),
+    ("isdigit", "ctype.h"),
+    ("islower", "ctype.h"),
+    ("isspace", "ctype.h"),
+    ("isupper", "ctype.h"),
+    ("labs", "stdlib.h"),
+    ("log", "math.h"),
+    ("malloc", "stdlib.h"),
+    ("memcmp", "string.h"),
+    ("memcpy", "string.h"),
+    ("memmove", "string.h"),
+    ("memset", "string.h"),
+    ("perror", "stdio.h"),
+    ("pow", "math.h"),
+    ("printf", "stdio.h"),
+    ("putc", "stdio.h"),
+    ("putchar", "stdio.h"),
+    ("puts", "stdio.h"),
+    ("qsort", "stdlib.h"),
+    ("rand", "stdlib.h"),
+    ("realloc", "stdlib.h"),
+    ("scanf", "stdio.h"),
+    ("sin", "math.h"),
+    ("snprintf", "stdio.h"),
+    ("sprintf", "stdio.h"),
+    ("sqrt", "math.h"),
+    ("srand", "stdlib.h"),
+    ("sscanf", "stdio.h"),
+    ("strcat", "string.h"),
+    ("strchr", "string.h"),
+    ("strcmp", "string.h"),
+    ("strcpy", "string.h"),
+    ("strdup", "string.h"),
+    ("strerror", "string.h"),
+    ("strlen", "string.h"),
+    ("strncat", "string.h"),
+    ("strncmp", "string.h"),
+    ("strncpy", "string.h"),
+    ("strrchr", "string.h"),
+    ("strstr", "string.h"),
+    ("strtod", "stdlib.h"),
+    ("strtok", "string.h"),
+    ("strtol", "stdlib.h"),
+    ("strtoul", "stdlib.h"),
+    ("system", "stdlib.h"),
+    ("tan", "math.h"),
+    ("time", "time.h"),
+    ("tolower", "ctype.h"),
+    ("toupper", "ctype.h"),
+    ("vfprintf", "stdio.h"),
+    ("vprintf", "stdio.h"),
+    ("vsnprintf", "stdio.h"),
+];
+
+/// The header that declares a well known library function
+pub fn header_for(name: &str) -> Option<&'static str> {
+    KNOWN_FUNCTIONS
+        .binary_search_by(|(known, _)| (*known).cmp(name))
+        .ok()
+        .map(|index| KNOWN_FUNCTIONS[index].1)
+}
+
+/// How to report a call to an undeclared function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImplicitDeclaration {
+    /// An error normally, only a warning when implicit declarations are
+    /// allowed for legacy code
+    pub is_error: bool,
+    pub message: String,
+    pub help: String,
+}
+
+pub fn implicit_declaration(name: &str, allow_legacy: bool) -> ImplicitDeclaration {
+    let message = if allow_legacy {
+        format!("implicit declaration of function `{}`", name)
+    } else {
+        format!(
+            "call to undeclared function `{}`; ISO C99 and later do not support implicit function declarations",
+            name
+        )
+    };
+
+    let help = match header_for(name) {
+        Some(header) => format!(
+            "include the header <{}> or explicitly provide a declaration for `{}`",
+            header, name
+        ),
+        None => format!("provide a declaration for `{}` before calling it", name),
+    };
+
+    ImplicitDeclaration {
+        is_error: !allow_legacy,
+        message,
+        help,
+    }
+}
+
+/// The type a function gets when it is implicitly declared in legacy mode,
+/// which is `int ()`
+pub fn implicit_function_type(ctx: &mut TypeContext) -> Type {
+    let int = ctx.int(IntKind::Int);
+
+    ctx.intern(TypeKind::Function {
+        ret: int,
+        params: Vec::new(),
+        variadic: false,
+        prototyped: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_is_sorted() {
+        assert!(KNOWN_FUNCTIONS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn suggests_header() {
+        assert_eq!(header_for("printf"), Some("stdio.h"));
+        assert_eq!(header_for("strlen"), Some("string.h"));
+        assert_eq!(header_for("my_helper"), None);
+
+        let diag = implicit_declaration("malloc", false);
+
+        assert!(diag.is_error);
+        assert!(diag.help.contains("<stdlib.h>"));
+        assert!(!implicit_declaration("malloc", true).is_error);
+    }
+}
@@ -0,0 +1,307 @@
+//! Resolution of `goto` targets within a function body (C11 6.8.6.1).
+//!
+//! Labels have function scope, so a `goto` may name a label that only appears
+//! later in the body, and nothing can be reported about a name until the whole
+//! function has been seen. The one thing that does depend on block structure is
+//! that a jump may not enter the scope of a variably modified object from
+//! outside of it, since the jump would skip the code that allocates it.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelDiagnosticKind {
+    /// A `goto` naming a label that the function never defines
+    Undefined(String),
+    Redefinition {
+        name: String,
+        previous: Span,
+    },
+    /// A label that no `goto` refers to
+    Unused(String),
+    /// A `goto` that would jump past the declaration of the variably modified
+    /// object `vla` into its scope
+    IntoVlaScope {
+        label: String,
+        vla: String,
+        vla_span: Span,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelDiagnostic {
+    pub kind: LabelDiagnosticKind,
+    pub span: Span,
+}
+
+impl LabelDiagnostic {
+    /// Whether this is a constraint violation rather than just suspicious
+    pub fn is_error(&self) -> bool {
+        !matches!(self.kind, LabelDiagnosticKind::Unused(_))
+    }
+}
+
+impl fmt::Display for LabelDiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undefined(name) => write!(f, "use of undeclared label `{}`", name),
+            Self::Redefinition { name, .. } => write!(f, "redefinition of label `{}`", name),
+            Self::Unused(name) => write!(f, "unused label `{}`", name),
+            Self::IntoVlaScope { label, vla, .. } => write!(
+                f,
+                "cannot jump to label `{}` into the scope of variably modified `{}`",
+                label, vla
+            ),
+        }
+    }
+}
+
+/// A variably modified object, identified by its index in
+/// [`LabelChecker::vlas`]
+type VlaId = usize;
+
+#[derive(Debug)]
+struct Label {
+    span: Span,
+    /// The variably modified objects in scope at the label
+    vlas: Vec<VlaId>,
+    used: bool,
+}
+
+#[derive(Debug)]
+struct Goto {
+    name: String,
+    span: Span,
+    /// The variably modified objects in scope at the `goto`
+    vlas: Vec<VlaId>,
+}
+
+/// Collects the labels and `goto` statements of a single function body
+#[derive(Debug, Default)]
+pub struct LabelChecker {
+    labels: HashMap<String, Label>,
+    /// Every `goto` in source order, checked once all labels are known
+    gotos: Vec<Goto>,
+    /// The name and declaring span of every variably modified object seen
+    vlas: Vec<(String, Span)>,
+    /// The variably modified objects currently in scope, outermost first
+    active: Vec<VlaId>,
+    /// How long `active` was when each enclosing block was entered
+    blocks: Vec<usize>,
+    diagnostics: Vec<LabelDiagnostic>,
+}
+
+impl LabelChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enter_block(&mut self) {
+        self.blocks.push(self.active.len());
+    }
+
+    pub fn leave_block(&mut self) {
+        let len = self.blocks.pop().expect("no block to leave");
+        self.active.truncate(len);
+    }
+
+    /// Records the declaration of an object with variably modified type, whose
+    /// scope extends to the end of the current block
+    pub fn declare_vla(&mut self, name: &str, span: Span) {
+        self.active.push(self.vlas.len());
+        self.vlas.push((name.to_owned(), span));
+    }
+
+    pub fn define_label(&mut self, name: &str, span: Span) {
+        if let Some(previous) = self.labels.get(name) {
+            self.diagnostics.push(LabelDiagnostic {
+                kind: LabelDiagnosticKind::Redefinition {
+                    name: name.to_owned(),
+                    previous: previous.span,
+                },
+                span,
+            });
+
+            return;
+        }
+
+        self.labels.insert(
+            name.to_owned(),
+            Label {
+                span,
+                vlas: self.active.clone(),
+                used: false,
+            },
+        );
+    }
+
+    pub fn add_goto(&mut self, name: &str, span: Span) {
+        self.gotos.push(Goto {
+            name: name.to_owned(),
+            span,
+            vlas: self.active.clone(),
+        });
+    }
+
+    /// Finishes the function body, returning everything found.
+    ///
+    /// Redefined labels come first in source order, since they're found as the
+    /// body is walked. Problems with `goto` statements follow in source order,
+    /// and then unused labels in the order they were defined.
+    pub fn finish(mut self) -> Vec<LabelDiagnostic> {
+        debug_assert!(self.blocks.is_empty(), "function ended inside a block");
+
+        for goto in &self.gotos {
+            let label = match self.labels.get_mut(&goto.name) {
+                Some(label) => label,
+                None => {
+                    self.diagnostics.push(LabelDiagnostic {
+                        kind: LabelDiagnosticKind::Undefined(goto.name.clone()),
+                        span: goto.span,
+                    });
+
+                    continue;
+                }
+            };
+
+            label.used = true;
+
+            // Scopes nest, so the jump leaves some objects' scopes and is fine
+            // as long as it doesn't enter any, which is exactly when every
+            // object in scope at the label is also in scope at the goto
+            if let Some(&entered) = label.vlas.iter().find(|id| !goto.vlas.contains(id)) {
+                let (vla, vla_span) = self.vlas[entered].clone();
+
+                self.diagnostics.push(LabelDiagnostic {
+                    kind: LabelDiagnosticKind::IntoVlaScope {
+                        label: goto.name.clone(),
+                        vla,
+                        vla_span,
+                    },
+                    span: goto.span,
+                });
+            }
+        }
+
+        let mut unused: Vec<_> = self
+            .labels
+            .into_iter()
+            .filter(|(_, label)| !label.used)
+            .collect();
+
+        unused.sort_by_key(|(_, label)| label.span);
+
+        self.diagnostics
+            .extend(unused.into_iter().map(|(name, label)| LabelDiagnostic {
+                kind: LabelDiagnosticKind::Unused(name),
+                span: label.span,
+            }));
+
+        self.diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(lo: usize) -> Span {
+        Span::new(0, lo, lo + 1)
+    }
+
+    #[test]
+    fn forward_and_backward_gotos() {
+        let mut checker = LabelChecker::new();
+
+        checker.define_label("top", span(0));
+        checker.add_goto("end", span(1));
+        checker.add_goto("top", span(2));
+        checker.add_goto("nowhere", span(3));
+        checker.define_label("end", span(4));
+        checker.define_label("end", span(5));
+        checker.define_label("spare", span(6));
+
+        let diagnostics = checker.finish();
+        let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind.clone()).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                LabelDiagnosticKind::Redefinition {
+                    name: String::from("end"),
+                    previous: span(4)
+                },
+                LabelDiagnosticKind::Undefined(String::from("nowhere")),
+                LabelDiagnosticKind::Unused(String::from("spare")),
+            ]
+        );
+        assert!(!diagnostics[2].is_error());
+    }
+
+    #[test]
+    fn jump_into_vla_scope() {
+        let mut checker = LabelChecker::new();
+
+        // goto inside; { int a[n]; inside: ; }
+        checker.add_goto("inside", span(0));
+        checker.enter_block();
+        checker.declare_vla("a", span(1));
+        checker.define_label("inside", span(2));
+        checker.leave_block();
+
+        let diagnostics = checker.finish();
+
+        assert_eq!(
+            diagnostics[0].kind,
+            LabelDiagnosticKind::IntoVlaScope {
+                label: String::from("inside"),
+                vla: String::from("a"),
+                vla_span: span(1)
+            }
+        );
+        assert_eq!(
+            diagnostics[0].kind.to_string(),
+            "cannot jump to label `inside` into the scope of variably modified `a`"
+        );
+    }
+
+    #[test]
+    fn jump_within_or_out_of_vla_scope() {
+        let mut checker = LabelChecker::new();
+
+        // { int a[n]; again: goto again; goto out; } out: ;
+        checker.enter_block();
+        checker.declare_vla("a", span(0));
+        checker.define_label("again", span(1));
+        checker.add_goto("again", span(2));
+        checker.add_goto("out", span(3));
+        checker.leave_block();
+        checker.define_label("out", span(4));
+
+        assert!(checker.finish().is_empty());
+    }
+
+    #[test]
+    fn jump_to_sibling_block() {
+        let mut checker = LabelChecker::new();
+
+        // { int a[n]; goto other; } { int b[n]; other: ; }
+        checker.enter_block();
+        checker.declare_vla("a", span(0));
+        checker.add_goto("other", span(1));
+        checker.leave_block();
+        checker.enter_block();
+        checker.declare_vla("b", span(2));
+        checker.define_label("other", span(3));
+        checker.leave_block();
+
+        let diagnostics = checker.finish();
+
+        assert!(matches!(
+            &diagnostics[0].kind,
+            LabelDiagnosticKind::IntoVlaScope { vla, .. } if vla == "b"
+        ));
+    }
+}
@@ -0,0 +1,316 @@
+//! Storage class specifiers and linkage (C11 6.2.2 and 6.7.1).
+//!
+//! Whether two declarations of the same name refer to the same entity depends
+//! on their linkage, which in turn depends on the storage class, the scope of
+//! the declaration, and any earlier declaration of the name that is visible.
+
+use std::fmt;
+
+use super::scope::ScopeKind;
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+    Typedef,
+    Extern,
+    Static,
+    Auto,
+    Register,
+}
+
+impl fmt::Display for StorageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Typedef => "typedef",
+            Self::Extern => "extern",
+            Self::Static => "static",
+            Self::Auto => "auto",
+            Self::Register => "register",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    /// Shared with every translation unit in the program
+    External,
+    /// Shared only within this translation unit
+    Internal,
+    /// Every declaration denotes a distinct entity
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClassError {
+    /// More than one storage class in a single declaration
+    Multiple {
+        first: StorageClass,
+        first_span: Span,
+    },
+    /// A storage class that isn't allowed where the declaration appears, like
+    /// `auto` at file scope or `static` on a parameter
+    NotAllowedHere(StorageClass),
+    /// A function declared at block scope with a storage class other than
+    /// `extern`
+    InvalidForBlockFunction(StorageClass),
+}
+
+impl fmt::Display for StorageClassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Multiple { .. } => {
+                write!(f, "multiple storage classes in declaration specifiers")
+            }
+            Self::NotAllowedHere(class) => write!(f, "`{}` is not allowed here", class),
+            Self::InvalidForBlockFunction(class) => write!(
+                f,
+                "function declared in block scope cannot have `{}` storage class",
+                class
+            ),
+        }
+    }
+}
+
+/// Two declarations of the same name disagreeing about its linkage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkageConflict {
+    /// `static int x;` after `int x;` or `extern int x;`
+    StaticFollowsNonStatic,
+    /// `int x;` at file scope after `static int x;`
+    NonStaticFollowsStatic,
+    /// `extern int x;` after a block scope `int x;` in the same block
+    ExternFollowsNoLinkage,
+    /// `int x;` after `extern int x;` in the same block
+    NoLinkageFollowsExtern,
+}
+
+impl fmt::Display for LinkageConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::StaticFollowsNonStatic => "static declaration follows non-static declaration",
+            Self::NonStaticFollowsStatic => "non-static declaration follows static declaration",
+            Self::ExternFollowsNoLinkage => "extern declaration follows non-extern declaration",
+            Self::NoLinkageFollowsExtern => "non-extern declaration follows extern declaration",
+        })
+    }
+}
+
+/// Picks the single storage class of a declaration out of its specifiers,
+/// given in source order along with their spans
+pub fn storage_class(
+    specifiers: &[(StorageClass, Span)],
+) -> Result<Option<StorageClass>, (StorageClassError, Span)> {
+    match specifiers {
+        [] => Ok(None),
+        [(class, _)] => Ok(Some(*class)),
+        [(first, first_span), (_, span), ..] => Err((
+            StorageClassError::Multiple {
+                first: *first,
+                first_span: *first_span,
+            },
+            *span,
+        )),
+    }
+}
+
+/// Checks that a storage class may be used on a declaration in the given scope
+pub fn check_storage_class(
+    class: StorageClass,
+    scope: ScopeKind,
+    is_function: bool,
+) -> Result<(), StorageClassError> {
+    use StorageClass::*;
+
+    match scope {
+        ScopeKind::File => match class {
+            Auto | Register => Err(StorageClassError::NotAllowedHere(class)),
+            Typedef | Extern | Static => Ok(()),
+        },
+        ScopeKind::Function | ScopeKind::Block if is_function => match class {
+            Typedef | Extern => Ok(()),
+            Static | Auto | Register => Err(StorageClassError::InvalidForBlockFunction(class)),
+        },
+        ScopeKind::Function | ScopeKind::Block => Ok(()),
+        // Parameters may only be `register` (C11 6.7.6.3p2)
+        ScopeKind::Prototype => match class {
+            Register => Ok(()),
+            _ => Err(StorageClassError::NotAllowedHere(class)),
+        },
+        ScopeKind::Record => Err(StorageClassError::NotAllowedHere(class)),
+    }
+}
+
+/// Where a declaration appears and what it declares, which along with any prior
+/// declaration determines its linkage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeclContext {
+    pub storage: Option<StorageClass>,
+    pub scope: ScopeKind,
+    pub is_function: bool,
+}
+
+/// A previous declaration of the same name that is visible at this point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorDecl {
+    pub linkage: Linkage,
+    /// Whether it was declared in the same scope as the new declaration, rather
+    /// than an enclosing one
+    pub same_scope: bool,
+}
+
+/// Determines the linkage of an identifier (C11 6.2.2), diagnosing
+/// declarations that disagree with an earlier one in the same scope.
+///
+/// Typedef names have no linkage and are never in conflict here; redefining
+/// them is a matter for the redeclaration checks.
+pub fn linkage(decl: DeclContext, prior: Option<PriorDecl>) -> Result<Linkage, LinkageConflict> {
+    let file_scope = decl.scope == ScopeKind::File;
+
+    let linkage = match decl.storage {
+        Some(StorageClass::Typedef) => return Ok(Linkage::None),
+        Some(StorageClass::Static) if file_scope => Linkage::Internal,
+        // `extern` (and no storage class on a function) takes the linkage of
+        // a visible prior declaration if it has one
+        Some(StorageClass::Extern) => inherited_linkage(prior),
+        None if decl.is_function => inherited_linkage(prior),
+        None if file_scope => Linkage::External,
+        _ => Linkage::None,
+    };
+
+    let prior = match prior {
+        Some(prior) if prior.same_scope => prior,
+        _ => return Ok(linkage),
+    };
+
+    match (prior.linkage, linkage) {
+        (Linkage::External, Linkage::Internal) => Err(LinkageConflict::StaticFollowsNonStatic),
+        (Linkage::Internal, Linkage::External) => Err(LinkageConflict::NonStaticFollowsStatic),
+        (Linkage::None, Linkage::External | Linkage::Internal) if !file_scope => {
+            Err(LinkageConflict::ExternFollowsNoLinkage)
+        }
+        (Linkage::External | Linkage::Internal, Linkage::None) if !file_scope => {
+            Err(LinkageConflict::NoLinkageFollowsExtern)
+        }
+        _ => Ok(linkage),
+    }
+}
+
+fn inherited_linkage(prior: Option<PriorDecl>) -> Linkage {
+    match prior {
+        Some(PriorDecl {
+            linkage: linkage @ (Linkage::External | Linkage::Internal),
+            ..
+        }) => linkage,
+        _ => Linkage::External,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decl(storage: Option<StorageClass>, scope: ScopeKind, is_function: bool) -> DeclContext {
+        DeclContext {
+            storage,
+            scope,
+            is_function,
+        }
+    }
+
+    fn prior(linkage: Linkage, same_scope: bool) -> Option<PriorDecl> {
+        Some(PriorDecl {
+            linkage,
+            same_scope,
+        })
+    }
+
+    #[test]
+    fn specifier_placement() {
+        let span = Span::new(0, 0, 6);
+
+        assert_eq!(
+            storage_class(&[(StorageClass::Static, span), (StorageClass::Extern, span)]),
+            Err((
+                StorageClassError::Multiple {
+                    first: StorageClass::Static,
+                    first_span: span
+                },
+                span
+            ))
+        );
+        assert!(check_storage_class(StorageClass::Auto, ScopeKind::File, false).is_err());
+        assert!(check_storage_class(StorageClass::Static, ScopeKind::Block, true).is_err());
+        assert!(check_storage_class(StorageClass::Static, ScopeKind::Block, false).is_ok());
+        assert!(check_storage_class(StorageClass::Register, ScopeKind::Prototype, false).is_ok());
+        assert!(check_storage_class(StorageClass::Static, ScopeKind::Prototype, false).is_err());
+    }
+
+    #[test]
+    fn linkage_rules() {
+        use StorageClass::*;
+
+        let file = |storage| decl(storage, ScopeKind::File, false);
+
+        assert_eq!(linkage(file(None), None), Ok(Linkage::External));
+        assert_eq!(linkage(file(Some(Static)), None), Ok(Linkage::Internal));
+        assert_eq!(
+            linkage(decl(None, ScopeKind::Block, false), None),
+            Ok(Linkage::None)
+        );
+
+        // `static int x; extern int x;` keeps internal linkage
+        assert_eq!(
+            linkage(file(Some(Extern)), prior(Linkage::Internal, true)),
+            Ok(Linkage::Internal)
+        );
+
+        // A block scope `extern` picks up the file scope declaration
+        assert_eq!(
+            linkage(
+                decl(Some(Extern), ScopeKind::Block, false),
+                prior(Linkage::Internal, false)
+            ),
+            Ok(Linkage::Internal)
+        );
+
+        // So does a function declared without a storage class
+        assert_eq!(
+            linkage(
+                decl(None, ScopeKind::File, true),
+                prior(Linkage::Internal, true)
+            ),
+            Ok(Linkage::Internal)
+        );
+    }
+
+    #[test]
+    fn linkage_conflicts() {
+        use StorageClass::*;
+
+        let file = |storage| decl(storage, ScopeKind::File, false);
+        let block = |storage| decl(storage, ScopeKind::Block, false);
+
+        assert_eq!(
+            linkage(file(Some(Static)), prior(Linkage::External, true)),
+            Err(LinkageConflict::StaticFollowsNonStatic)
+        );
+        assert_eq!(
+            linkage(file(None), prior(Linkage::Internal, true)),
+            Err(LinkageConflict::NonStaticFollowsStatic)
+        );
+        assert_eq!(
+            linkage(block(Some(Extern)), prior(Linkage::None, true)),
+            Err(LinkageConflict::ExternFollowsNoLinkage)
+        );
+        assert_eq!(
+            linkage(block(None), prior(Linkage::External, true)),
+            Err(LinkageConflict::NoLinkageFollowsExtern)
+        );
+
+        // Shadowing in an inner scope is never a conflict
+        assert_eq!(
+            linkage(block(Some(Static)), prior(Linkage::External, false)),
+            Ok(Linkage::None)
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! Semantic analysis.
+
+pub mod compare;
+pub mod constants;
+pub mod conversions;
+pub mod declare;
+pub mod implicit;
+pub mod labels;
+pub mod linkage;
+pub mod qualifiers;
+pub mod scope;
+pub mod switch;
@@ -0,0 +1,326 @@
+//! Qualifier correctness: what may be modified, and how qualifiers flow from
+//! an object to the lvalues designating its parts.
+//!
+//! Assigning a pointer-to-const to a pointer-to-non-const is caught by
+//! [`assignment_conversion`](super::conversions::assignment_conversion). What's
+//! left is making sure `const` objects aren't written through an lvalue, and
+//! that `volatile` reaches every lvalue that designates a volatile object, so
+//! that IR lowering only has to look at the type of the lvalue it is accessing
+//! to know whether the access must be kept exactly as written.
+
+use std::fmt;
+
+use crate::types::{RecordKind, Type, TypeContext, TypeKind};
+
+/// Why an lvalue can't be assigned to or incremented (C11 6.3.2.1p1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotModifiable {
+    ConstQualified,
+    Array,
+    Incomplete,
+    /// A struct or union with a `const` member somewhere inside it, giving the
+    /// path to that member
+    ConstMember(RecordKind, Vec<String>),
+}
+
+impl fmt::Display for NotModifiable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConstQualified => {
+                write!(f, "cannot assign to a variable with const-qualified type")
+            }
+            Self::Array => write!(f, "array type is not assignable"),
+            Self::Incomplete => write!(f, "cannot assign to an object of incomplete type"),
+            Self::ConstMember(kind, path) => write!(
+                f,
+                "cannot assign to a {} with const-qualified member `{}`",
+                match kind {
+                    RecordKind::Struct => "struct",
+                    RecordKind::Union => "union",
+                },
+                path.join(".")
+            ),
+        }
+    }
+}
+
+/// Checks that an lvalue of type `ty` is a modifiable lvalue, which is what
+/// assignment, compound assignment, and `++`/`--` require
+pub fn check_modifiable(ctx: &TypeContext, ty: Type) -> Result<(), NotModifiable> {
+    if ty.quals.is_const {
+        return Err(NotModifiable::ConstQualified);
+    }
+
+    match ctx.kind(ty) {
+        TypeKind::Array { .. } => Err(NotModifiable::Array),
+        _ if !ctx.is_complete(ty) => Err(NotModifiable::Incomplete),
+        TypeKind::Record(kind, _) => match const_member_path(ctx, ty) {
+            Some(path) => Err(NotModifiable::ConstMember(*kind, path)),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Finds a const member of a record, recursing into members that are records
+/// themselves
+fn const_member_path(ctx: &TypeContext, ty: Type) -> Option<Vec<String>> {
+    let id = match ctx.kind(ty) {
+        TypeKind::Record(_, id) => *id,
+        _ => return None,
+    };
+
+    for member in ctx.record(id).members.as_ref()? {
+        // Unnamed bit-fields are only padding and can't be assigned anyway
+        if member.name.is_none() && member.bit_width.is_some() {
+            continue;
+        }
+
+        let name = match &member.name {
+            Some(name) => name,
+            // Anonymous members don't show up in the path used to access them,
+            // so a const one is named by the members it makes const
+            None if member.ty.quals.is_const => match first_named_member(ctx, member.ty) {
+                Some(path) => return Some(path),
+                None => continue,
+            },
+            None => match const_member_path(ctx, member.ty) {
+                Some(path) => return Some(path),
+                None => continue,
+            },
+        };
+
+        if member.ty.quals.is_const {
+            return Some(vec![name.clone()]);
+        }
+
+        if let Some(mut path) = const_member_path(ctx, member.ty) {
+            path.insert(0, name.clone());
+            return Some(path);
+        }
+    }
+
+    None
+}
+
+/// The path to the first member of a record that has a name, looking through
+/// anonymous members
+fn first_named_member(ctx: &TypeContext, ty: Type) -> Option<Vec<String>> {
+    let id = match ctx.kind(ty) {
+        TypeKind::Record(_, id) => *id,
+        _ => return None,
+    };
+
+    ctx.record(id)
+        .members
+        .as_ref()?
+        .iter()
+        .find_map(|member| match &member.name {
+            Some(name) => Some(vec![name.clone()]),
+            None if member.bit_width.is_some() => None,
+            None => first_named_member(ctx, member.ty),
+        })
+}
+
+/// The type of the lvalue `s.m` when `s` has type `record` and the member `m`
+/// was declared with type `member`. The member gets the qualifiers of the
+/// record on top of its own (C11 6.5.2.3p3), so a member of a `volatile struct`
+/// is accessed as volatile.
+pub fn member_access_type(record: Type, member: Type) -> Type {
+    member.with_quals(record.quals)
+}
+
+/// The type of the lvalue `*p`, which keeps the qualifiers of the pointed-to
+/// type so that writing through a `const int *` is rejected and reading
+/// through a `volatile int *` is a volatile access
+pub fn deref_type(ctx: &TypeContext, pointer: Type) -> Option<Type> {
+    match ctx.kind(pointer) {
+        TypeKind::Pointer(pointee) => Some(*pointee),
+        _ => None,
+    }
+}
+
+/// Whether accessing an lvalue of this type is a volatile access that later
+/// passes must neither remove, merge, nor reorder
+pub fn is_volatile_access(ty: Type) -> bool {
+    ty.quals.is_volatile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sema::conversions::decay;
+    use crate::target::TargetInfo;
+    use crate::types::{IntKind, Member, Qualifiers};
+
+    fn define_kind(
+        ctx: &mut TypeContext,
+        kind: RecordKind,
+        members: Vec<(Option<&str>, Type)>,
+    ) -> Type {
+        let (ty, id) = ctx.new_record(kind, None);
+
+        let members = members
+            .into_iter()
+            .map(|(name, ty)| Member {
+                name: name.map(str::to_owned),
+                ty,
+                bit_width: None,
+            })
+            .collect();
+
+        ctx.define_record(id, members);
+
+        ty
+    }
+
+    fn define(ctx: &mut TypeContext, members: Vec<(&str, Type)>) -> Type {
+        let members = members
+            .into_iter()
+            .map(|(name, ty)| (Some(name), ty))
+            .collect();
+
+        define_kind(ctx, RecordKind::Struct, members)
+    }
+
+    #[test]
+    fn modifiable_lvalues() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let const_int = int.with_quals(Qualifiers::CONST);
+        let array = ctx.array_of(int, Some(2));
+        let void = ctx.void();
+
+        assert_eq!(check_modifiable(&ctx, int), Ok(()));
+        assert_eq!(
+            check_modifiable(&ctx, const_int),
+            Err(NotModifiable::ConstQualified)
+        );
+        assert_eq!(check_modifiable(&ctx, array), Err(NotModifiable::Array));
+        assert_eq!(check_modifiable(&ctx, void), Err(NotModifiable::Incomplete));
+
+        let inner = define(&mut ctx, vec![("a", int), ("b", const_int)]);
+        let outer = define(&mut ctx, vec![("x", int), ("inner", inner)]);
+
+        assert_eq!(
+            check_modifiable(&ctx, outer),
+            Err(NotModifiable::ConstMember(
+                RecordKind::Struct,
+                vec![String::from("inner"), String::from("b")]
+            ))
+        );
+    }
+
+    #[test]
+    fn const_members_of_unions_and_anonymous_members() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let const_int = int.with_quals(Qualifiers::CONST);
+
+        let union = define_kind(
+            &mut ctx,
+            RecordKind::Union,
+            vec![(Some("i"), int), (Some("c"), const_int)],
+        );
+
+        assert_eq!(
+            check_modifiable(&ctx, union).unwrap_err().to_string(),
+            "cannot assign to a union with const-qualified member `c`"
+        );
+
+        // struct { int x; const struct { int y; }; }
+        let anonymous = define(&mut ctx, vec![("y", int)]);
+        let outer = define_kind(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![
+                (Some("x"), int),
+                (None, anonymous.with_quals(Qualifiers::CONST)),
+            ],
+        );
+
+        assert_eq!(
+            check_modifiable(&ctx, outer).unwrap_err().to_string(),
+            "cannot assign to a struct with const-qualified member `y`"
+        );
+    }
+
+    #[test]
+    fn qualifiers_propagate() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let record = define(&mut ctx, vec![("reg", int)]);
+        let volatile_record = record.with_quals(Qualifiers::VOLATILE);
+        let const_record = record.with_quals(Qualifiers::CONST);
+
+        assert!(is_volatile_access(member_access_type(volatile_record, int)));
+        assert!(check_modifiable(&ctx, member_access_type(const_record, int)).is_err());
+
+        let pointer = ctx.pointer_to(int.with_quals(Qualifiers::VOLATILE));
+
+        assert!(is_volatile_access(deref_type(&ctx, pointer).unwrap()));
+        assert_eq!(deref_type(&ctx, int), None);
+    }
+
+    #[test]
+    fn member_through_const_anonymous_member() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        // struct { const struct { int y; }; } t; t.y = 1;
+        let int = ctx.int(IntKind::Int);
+        let anonymous = define(&mut ctx, vec![("y", int)]);
+        let (outer, id) = ctx.new_record(RecordKind::Struct, None);
+
+        ctx.define_record(
+            id,
+            vec![Member {
+                name: None,
+                ty: anonymous.with_quals(Qualifiers::CONST),
+                bit_width: None,
+            }],
+        );
+
+        let (_, y) = ctx.lookup_member(id, "y").unwrap();
+
+        assert_eq!(
+            check_modifiable(&ctx, member_access_type(outer, y)),
+            Err(NotModifiable::ConstQualified)
+        );
+    }
+
+    /// The type of `s.arr[0]` where `s` has the given qualifiers and is a
+    /// `struct { int arr[3]; }`
+    fn array_member_element(quals: Qualifiers) -> (TypeContext, Type) {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let array = ctx.array_of(int, Some(3));
+        let record = define(&mut ctx, vec![("arr", array)]);
+
+        let member = member_access_type(record.with_quals(quals), array);
+        let (pointer, _) = decay(&mut ctx, member);
+        let element = deref_type(&ctx, pointer).unwrap();
+
+        (ctx, element)
+    }
+
+    #[test]
+    fn const_record_array_member() {
+        let (ctx, element) = array_member_element(Qualifiers::CONST);
+
+        assert_eq!(
+            check_modifiable(&ctx, element),
+            Err(NotModifiable::ConstQualified)
+        );
+    }
+
+    #[test]
+    fn volatile_record_array_member() {
+        let (_, element) = array_member_element(Qualifiers::VOLATILE);
+
+        assert!(is_volatile_access(element));
+    }
+}
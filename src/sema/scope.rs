@@ -0,0 +1,283 @@
+//! Lexically scoped symbol tables.
+//!
+//! C keeps four separate name spaces (C11 6.2.3): labels, tags, members of each
+//! struct or union, and everything else ("ordinary" identifiers). A name in an
+//! inner scope hides the same name in the same name space of an outer scope,
+//! but never a name in a different name space, so `struct s` and a variable `s`
+//! coexist happily.
+//!
+//! Labels are the odd one out: they have function scope rather than block
+//! scope, and `goto` may name one before it's defined, so they're resolved per
+//! function body by [`LabelChecker`](super::labels::LabelChecker) instead.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use crate::span::Span;
+
+/// The name spaces kept by a [`SymbolTable`]. The label name space is
+/// deliberately not one of them: it is tracked by
+/// [`LabelChecker`](super::labels::LabelChecker), which owns label definitions,
+/// uses and `goto` checks for a function body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    /// Objects, functions, typedef names and enumeration constants
+    Ordinary,
+    /// The names following `struct`, `union` and `enum`
+    Tag,
+    /// Members of the struct or union currently being defined
+    Member,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// The outermost scope of the translation unit
+    File,
+    /// The body of a function definition
+    Function,
+    /// A compound statement or the body of a selection/iteration statement
+    Block,
+    /// The parameter list of a function declarator that isn't a definition
+    Prototype,
+    /// The member list of a struct or union definition
+    Record,
+}
+
+/// A declared name along with where it was declared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol<T> {
+    pub name: String,
+    /// The span of the declaring site, for "previously declared here" notes
+    pub span: Span,
+    pub data: T,
+}
+
+#[derive(Debug)]
+pub struct Scope<T> {
+    kind: ScopeKind,
+    symbols: HashMap<Namespace, HashMap<String, Symbol<T>>>,
+}
+
+impl<T> Scope<T> {
+    fn new(kind: ScopeKind) -> Self {
+        Self {
+            kind,
+            symbols: HashMap::new(),
+        }
+    }
+
+    pub fn kind(&self) -> ScopeKind {
+        self.kind
+    }
+
+    pub fn get(&self, ns: Namespace, name: &str) -> Option<&Symbol<T>> {
+        self.symbols.get(&ns)?.get(name)
+    }
+
+    /// Iterates the symbols of one name space declared directly in this scope,
+    /// in no particular order
+    pub fn symbols(&self, ns: Namespace) -> impl Iterator<Item = &Symbol<T>> {
+        self.symbols.get(&ns).into_iter().flat_map(|s| s.values())
+    }
+
+    fn get_mut(&mut self, ns: Namespace, name: &str) -> Option<&mut Symbol<T>> {
+        self.symbols.get_mut(&ns)?.get_mut(name)
+    }
+}
+
+/// A stack of scopes, always containing at least the file scope
+#[derive(Debug)]
+pub struct SymbolTable<T> {
+    scopes: Vec<Scope<T>>,
+}
+
+impl<T> Default for SymbolTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SymbolTable<T> {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope::new(ScopeKind::File)],
+        }
+    }
+
+    pub fn push_scope(&mut self, kind: ScopeKind) {
+        debug_assert_ne!(kind, ScopeKind::File, "only one file scope may exist");
+        self.scopes.push(Scope::new(kind));
+    }
+
+    /// Pops the innermost scope and returns it, so that for example the members
+    /// of a record can be kept once its definition is complete.
+    ///
+    /// Panics if only the file scope is left.
+    pub fn pop_scope(&mut self) -> Scope<T> {
+        assert!(self.scopes.len() > 1, "attempted to pop the file scope");
+        self.scopes.pop().unwrap()
+    }
+
+    /// The kind of the innermost scope
+    pub fn current_kind(&self) -> ScopeKind {
+        self.scopes.last().unwrap().kind
+    }
+
+    pub fn is_file_scope(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    /// Declares `name` in the scope that owns the given name space.
+    ///
+    /// If the name is already declared in that same scope, nothing is inserted
+    /// and the existing symbol is returned, so that the caller can decide
+    /// whether the redeclaration is allowed and point at the earlier one if not.
+    /// Declarations in outer scopes are simply shadowed.
+    pub fn declare(
+        &mut self,
+        ns: Namespace,
+        name: &str,
+        span: Span,
+        data: T,
+    ) -> Result<(), &mut Symbol<T>> {
+        let index = self.owning_scope(ns);
+        let symbols = self.scopes[index].symbols.entry(ns).or_default();
+
+        match symbols.entry(name.to_owned()) {
+            Entry::Occupied(entry) => Err(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                entry.insert(Symbol {
+                    name: name.to_owned(),
+                    span,
+                    data,
+                });
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Looks up the innermost visible declaration of `name`
+    pub fn lookup(&self, ns: Namespace, name: &str) -> Option<&Symbol<T>> {
+        self.visible_scopes(ns)
+            .find_map(|index| self.scopes[index].get(ns, name))
+    }
+
+    pub fn lookup_mut(&mut self, ns: Namespace, name: &str) -> Option<&mut Symbol<T>> {
+        let index = self
+            .visible_scopes(ns)
+            .find(|&index| self.scopes[index].get(ns, name).is_some())?;
+
+        self.scopes[index].get_mut(ns, name)
+    }
+
+    /// Looks up `name` only in the scope that a declaration in `ns` would go
+    /// into right now, ignoring anything it would shadow
+    pub fn lookup_current(&self, ns: Namespace, name: &str) -> Option<&Symbol<T>> {
+        self.scopes[self.owning_scope(ns)].get(ns, name)
+    }
+
+    /// The index of the scope that declarations in `ns` are placed in.
+    ///
+    /// Ordinary identifiers and tags declared inside a struct definition belong
+    /// to the scope enclosing the struct, since a struct body doesn't open a
+    /// scope of its own for them.
+    fn owning_scope(&self, ns: Namespace) -> usize {
+        let top = self.scopes.len() - 1;
+
+        match ns {
+            Namespace::Member => {
+                debug_assert_eq!(self.scopes[top].kind, ScopeKind::Record);
+                top
+            }
+            Namespace::Ordinary | Namespace::Tag => self
+                .scopes
+                .iter()
+                .rposition(|s| s.kind != ScopeKind::Record)
+                .unwrap(),
+        }
+    }
+
+    /// The indices of the scopes searched for names in `ns`, innermost first
+    fn visible_scopes(&self, ns: Namespace) -> impl Iterator<Item = usize> + '_ {
+        let top = self.scopes.len() - 1;
+
+        (0..=top).rev().filter(move |&index| {
+            let kind = self.scopes[index].kind;
+
+            match ns {
+                // Members are only found through their record, or while it is
+                // still being defined
+                Namespace::Member => index == top && kind == ScopeKind::Record,
+                Namespace::Ordinary | Namespace::Tag => kind != ScopeKind::Record,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(lo: usize) -> Span {
+        Span::new(0, lo, lo + 1)
+    }
+
+    #[test]
+    fn shadowing() {
+        let mut table = SymbolTable::new();
+
+        table.declare(Namespace::Ordinary, "x", span(0), 1).unwrap();
+        table.push_scope(ScopeKind::Block);
+        table.declare(Namespace::Ordinary, "x", span(5), 2).unwrap();
+
+        assert_eq!(table.lookup(Namespace::Ordinary, "x").unwrap().data, 2);
+
+        table.pop_scope();
+
+        assert_eq!(table.lookup(Namespace::Ordinary, "x").unwrap().data, 1);
+    }
+
+    #[test]
+    fn redeclaration_returns_previous() {
+        let mut table = SymbolTable::new();
+
+        table
+            .declare(Namespace::Ordinary, "x", span(0), ())
+            .unwrap();
+
+        let previous = table
+            .declare(Namespace::Ordinary, "x", span(9), ())
+            .unwrap_err();
+
+        assert_eq!(previous.span, span(0));
+    }
+
+    #[test]
+    fn namespaces_are_separate() {
+        let mut table = SymbolTable::new();
+
+        table.declare(Namespace::Tag, "s", span(0), "tag").unwrap();
+        table
+            .declare(Namespace::Ordinary, "s", span(1), "var")
+            .unwrap();
+
+        assert_eq!(table.lookup(Namespace::Tag, "s").unwrap().data, "tag");
+        assert_eq!(table.lookup(Namespace::Ordinary, "s").unwrap().data, "var");
+    }
+
+    #[test]
+    fn record_scope_holds_only_members() {
+        let mut table = SymbolTable::new();
+
+        table.push_scope(ScopeKind::Record);
+        table.declare(Namespace::Member, "x", span(0), ()).unwrap();
+        table.declare(Namespace::Tag, "inner", span(1), ()).unwrap();
+
+        let members = table.pop_scope();
+
+        assert!(members.get(Namespace::Member, "x").is_some());
+        assert!(table.lookup(Namespace::Member, "x").is_none());
+        assert!(table.lookup(Namespace::Tag, "inner").is_some());
+    }
+}
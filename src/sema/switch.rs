@@ -0,0 +1,366 @@
+//! Checks on the labels of `switch` statements (C11 6.8.4.2).
+//!
+//! Each case value is converted to the promoted type of the controlling
+//! expression, so two cases can collide even when they're written differently,
+//! and a case can silently change value if it doesn't fit.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::conversions::promote;
+use crate::span::Span;
+use crate::target::TargetInfo;
+use crate::types::{IntKind, Type, TypeContext, TypeKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwitchDiagnosticKind {
+    /// A controlling expression without integer type, like `switch (1.5)`,
+    /// naming the type it has
+    NonIntegerCondition(String),
+    /// A case value that changes when converted to the controlling type
+    CaseOutOfRange {
+        value: i128,
+        converted: i128,
+    },
+    /// Two cases with the same value once converted
+    DuplicateCase {
+        value: i128,
+        previous: Span,
+    },
+    DuplicateDefault {
+        previous: Span,
+    },
+    /// A `case` or `default` label that isn't inside any switch
+    OutsideSwitch,
+    /// A switch over an enumeration without a `default` that doesn't handle
+    /// all of its enumerators, listing the missing ones in declaration order
+    MissingEnumerators(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchDiagnostic {
+    pub kind: SwitchDiagnosticKind,
+    pub span: Span,
+}
+
+impl SwitchDiagnostic {
+    /// Whether this is a constraint violation rather than just suspicious
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self.kind,
+            SwitchDiagnosticKind::CaseOutOfRange { .. }
+                | SwitchDiagnosticKind::MissingEnumerators(_)
+        )
+    }
+}
+
+impl fmt::Display for SwitchDiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonIntegerCondition(ty) => {
+                write!(f, "switch condition has non-integer type `{}`", ty)
+            }
+            Self::CaseOutOfRange { value, converted } => write!(
+                f,
+                "case value {} is out of range of the switch condition type and becomes {}",
+                value, converted
+            ),
+            Self::DuplicateCase { value, .. } => write!(f, "duplicate case value {}", value),
+            Self::DuplicateDefault { .. } => {
+                write!(f, "multiple default labels in one switch")
+            }
+            Self::OutsideSwitch => write!(f, "label not within a switch statement"),
+            Self::MissingEnumerators(names) => {
+                let names: Vec<_> = names.iter().map(|n| format!("`{}`", n)).collect();
+
+                if names.len() == 1 {
+                    write!(f, "enumeration value {} not handled in switch", names[0])
+                } else {
+                    write!(
+                        f,
+                        "enumeration values {} not handled in switch",
+                        names.join(", ")
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Collects the labels of a single switch statement
+#[derive(Debug)]
+pub struct SwitchChecker {
+    span: Span,
+    /// The promoted type of the controlling expression, or `None` if it doesn't
+    /// have integer type and the case values can't be converted
+    kind: Option<IntKind>,
+    target: TargetInfo,
+    cases: HashMap<i128, Span>,
+    default: Option<Span>,
+    diagnostics: Vec<SwitchDiagnostic>,
+}
+
+impl SwitchChecker {
+    /// Starts checking a switch whose controlling expression has type
+    /// `controlling`, with `span` covering the `switch` keyword and condition.
+    ///
+    /// A condition without integer type is reported here, after which the
+    /// labels are still collected so that duplicates are found, but their
+    /// values are taken as written.
+    pub fn new(ctx: &mut TypeContext, controlling: Type, span: Span) -> Self {
+        let promoted = promote(ctx, controlling);
+        let mut diagnostics = Vec::new();
+
+        let kind = match ctx.kind(promoted) {
+            TypeKind::Integer(kind) => Some(*kind),
+            _ => {
+                diagnostics.push(SwitchDiagnostic {
+                    kind: SwitchDiagnosticKind::NonIntegerCondition(ctx.display(controlling)),
+                    span,
+                });
+
+                None
+            }
+        };
+
+        Self {
+            span,
+            kind,
+            target: ctx.target().clone(),
+            cases: HashMap::new(),
+            default: None,
+            diagnostics,
+        }
+    }
+
+    pub fn add_case(&mut self, value: i128, span: Span) {
+        let converted = match self.kind {
+            Some(kind) => kind.wrap(value, &self.target),
+            None => value,
+        };
+
+        if converted != value {
+            self.diagnostics.push(SwitchDiagnostic {
+                kind: SwitchDiagnosticKind::CaseOutOfRange { value, converted },
+                span,
+            });
+        }
+
+        if let Some(&previous) = self.cases.get(&converted) {
+            self.diagnostics.push(SwitchDiagnostic {
+                kind: SwitchDiagnosticKind::DuplicateCase { value, previous },
+                span,
+            });
+        } else {
+            self.cases.insert(converted, span);
+        }
+    }
+
+    pub fn add_default(&mut self, span: Span) {
+        match self.default {
+            Some(previous) => self.diagnostics.push(SwitchDiagnostic {
+                kind: SwitchDiagnosticKind::DuplicateDefault { previous },
+                span,
+            }),
+            None => self.default = Some(span),
+        }
+    }
+
+    /// Finishes the switch, returning the problems with the condition and labels
+    /// in source order, followed by any enumerators the switch doesn't handle,
+    /// which point back at the switch itself.
+    ///
+    /// `enumerators` are the names and values of the enumeration the
+    /// controlling expression had before promotion, if it had one.
+    pub fn finish(mut self, enumerators: Option<&[(String, i128)]>) -> Vec<SwitchDiagnostic> {
+        if let (Some(enumerators), None) = (enumerators, self.default) {
+            let missing: Vec<_> = enumerators
+                .iter()
+                .filter(|(_, value)| !self.cases.contains_key(value))
+                .map(|(name, _)| name.clone())
+                .collect();
+
+            if !missing.is_empty() {
+                self.diagnostics.push(SwitchDiagnostic {
+                    kind: SwitchDiagnosticKind::MissingEnumerators(missing),
+                    span: self.span,
+                });
+            }
+        }
+
+        self.diagnostics
+    }
+}
+
+/// The switch statements enclosing the current statement, innermost last,
+/// which is where `case` and `default` labels go
+#[derive(Debug, Default)]
+pub struct SwitchStack {
+    switches: Vec<SwitchChecker>,
+}
+
+impl SwitchStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, checker: SwitchChecker) {
+        self.switches.push(checker);
+    }
+
+    /// Ends the innermost switch, see [`SwitchChecker::finish`]
+    pub fn pop(&mut self, enumerators: Option<&[(String, i128)]>) -> Vec<SwitchDiagnostic> {
+        self.switches
+            .pop()
+            .expect("no switch statement to end")
+            .finish(enumerators)
+    }
+
+    pub fn add_case(&mut self, value: i128, span: Span) -> Result<(), SwitchDiagnostic> {
+        self.innermost(span)?.add_case(value, span);
+        Ok(())
+    }
+
+    pub fn add_default(&mut self, span: Span) -> Result<(), SwitchDiagnostic> {
+        self.innermost(span)?.add_default(span);
+        Ok(())
+    }
+
+    fn innermost(&mut self, span: Span) -> Result<&mut SwitchChecker, SwitchDiagnostic> {
+        self.switches.last_mut().ok_or(SwitchDiagnostic {
+            kind: SwitchDiagnosticKind::OutsideSwitch,
+            span,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FloatKind;
+
+    fn span(lo: usize) -> Span {
+        Span::new(0, lo, lo + 1)
+    }
+
+    #[test]
+    fn duplicates_after_conversion() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let uint = ctx.int(IntKind::UInt);
+        let mut checker = SwitchChecker::new(&mut ctx, uint, span(0));
+
+        checker.add_case(1, span(1));
+        checker.add_case(-1, span(2));
+        checker.add_case(0xFFFF_FFFF, span(3));
+        checker.add_default(span(4));
+        checker.add_default(span(5));
+
+        let kinds: Vec<_> = checker.finish(None).into_iter().map(|d| d.kind).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                SwitchDiagnosticKind::CaseOutOfRange {
+                    value: -1,
+                    converted: 0xFFFF_FFFF
+                },
+                SwitchDiagnosticKind::DuplicateCase {
+                    value: 0xFFFF_FFFF,
+                    previous: span(2)
+                },
+                SwitchDiagnosticKind::DuplicateDefault { previous: span(4) },
+            ]
+        );
+    }
+
+    #[test]
+    fn char_switch_is_promoted() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let char = ctx.int(IntKind::Char);
+        let mut checker = SwitchChecker::new(&mut ctx, char, span(0));
+
+        // 300 fits in the promoted int, so isn't converted
+        checker.add_case(300, span(1));
+
+        assert!(checker.finish(None).is_empty());
+    }
+
+    #[test]
+    fn missing_enumerators() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let int = ctx.int(IntKind::Int);
+        let colors = [
+            (String::from("RED"), 0),
+            (String::from("GREEN"), 1),
+            (String::from("BLUE"), 2),
+        ];
+
+        let mut checker = SwitchChecker::new(&mut ctx, int, span(0));
+        checker.add_case(1, span(1));
+
+        let diagnostics = checker.finish(Some(&colors));
+
+        assert_eq!(
+            diagnostics[0].kind,
+            SwitchDiagnosticKind::MissingEnumerators(vec![
+                String::from("RED"),
+                String::from("BLUE")
+            ])
+        );
+        assert_eq!(
+            diagnostics[0].kind.to_string(),
+            "enumeration values `RED`, `BLUE` not handled in switch"
+        );
+
+        let mut checker = SwitchChecker::new(&mut ctx, int, span(0));
+        checker.add_default(span(1));
+
+        assert!(checker.finish(Some(&colors)).is_empty());
+    }
+
+    #[test]
+    fn non_integer_condition() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let double = ctx.float(FloatKind::Double);
+        let mut checker = SwitchChecker::new(&mut ctx, double, span(0));
+
+        checker.add_case(1, span(1));
+        checker.add_case(1, span(2));
+
+        let diagnostics = checker.finish(None);
+
+        assert_eq!(
+            diagnostics[0].kind,
+            SwitchDiagnosticKind::NonIntegerCondition(String::from("double"))
+        );
+        assert_eq!(diagnostics[0].span, span(0));
+        assert!(diagnostics[0].is_error());
+        assert_eq!(
+            diagnostics[1].kind,
+            SwitchDiagnosticKind::DuplicateCase {
+                value: 1,
+                previous: span(1)
+            }
+        );
+    }
+
+    #[test]
+    fn case_outside_switch() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+        let int = ctx.int(IntKind::Int);
+        let mut stack = SwitchStack::new();
+
+        assert_eq!(
+            stack.add_case(1, span(0)).unwrap_err().kind,
+            SwitchDiagnosticKind::OutsideSwitch
+        );
+
+        stack.push(SwitchChecker::new(&mut ctx, int, span(1)));
+        stack.add_case(1, span(2)).unwrap();
+        stack.push(SwitchChecker::new(&mut ctx, int, span(3)));
+        stack.add_case(1, span(4)).unwrap();
+
+        assert!(stack.pop(None).is_empty());
+        assert!(stack.pop(None).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Source locations.
+//!
+//! Spans always count bytes of UTF-8 source text, which is what the lexer
+//! indexes by and what slicing a `str` expects. Characters only matter when
+//! lining up carets under a snippet, so they are worked out at that point with
+//! [`Span::char_columns`] and nowhere else.
+
+/// A region of source text, identified by the index of the source it belongs
+/// to and a half-open range of byte offsets into that source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Span {
+    pub source: usize,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Span {
+    pub fn new(source: usize, lo: usize, hi: usize) -> Self {
+        debug_assert!(lo <= hi, "span start {} is past its end {}", lo, hi);
+        Self { source, lo, hi }
+    }
+
+    /// The length of this span in bytes
+    pub fn len(&self) -> usize {
+        self.hi - self.lo
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    /// Returns a span covering `len` bytes starting `offset` bytes into this one
+    pub fn subspan(&self, offset: usize, len: usize) -> Self {
+        debug_assert!(offset + len <= self.len());
+        Self::new(self.source, self.lo + offset, self.lo + offset + len)
+    }
+
+    /// Converts this span into a half-open range of character offsets from the
+    /// start of `text`, the source it belongs to. Carets under a snippet want
+    /// columns within a line instead, which [`char_columns`](Self::char_columns)
+    /// gives.
+    ///
+    /// Both ends must fall on character boundaries.
+    pub fn char_range(&self, text: &str) -> (usize, usize) {
+        debug_assert!(
+            text.is_char_boundary(self.lo) && text.is_char_boundary(self.hi),
+            "span {}..{} splits a character",
+            self.lo,
+            self.hi
+        );
+
+        let lo = text[..self.lo].chars().count();
+        let hi = lo + text[self.lo..self.hi].chars().count();
+
+        (lo, hi)
+    }
+
+    /// Converts this span into a half-open range of character columns within
+    /// one line of its source, for placing carets under that line.
+    ///
+    /// `line` is the text of the line without its terminator and `line_start`
+    /// the byte offset it starts at in the source. The span must start on the
+    /// line, and if it continues past the end of it the range stops at the end
+    /// of the line.
+    pub fn char_columns(&self, line: &str, line_start: usize) -> (usize, usize) {
+        debug_assert!(
+            (line_start..=line_start + line.len()).contains(&self.lo),
+            "span {}..{} doesn't start on the line at {}",
+            self.lo,
+            self.hi,
+            line_start
+        );
+
+        let lo = self.lo - line_start;
+        let hi = (self.hi - line_start).min(line.len());
+
+        Self::new(self.source, lo, hi).char_range(line)
+    }
+
+    /// Returns a span covering both this span and `other` along with everything
+    /// in between, like a whole expression from its first and last tokens
+    pub fn to(&self, other: Span) -> Self {
+        debug_assert_eq!(self.source, other.source, "spans are in different sources");
+        Self::new(self.source, self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    /// Returns the span from the end of this span to the start of `other`, which
+    /// must not start before this one ends
+    pub fn between(&self, other: Span) -> Self {
+        debug_assert_eq!(self.source, other.source, "spans are in different sources");
+        Self::new(self.source, self.hi, other.lo)
+    }
+
+    /// Returns the empty span at the start of this one
+    pub fn shrink_to_lo(&self) -> Self {
+        Self::new(self.source, self.lo, self.lo)
+    }
+
+    /// Returns the empty span at the end of this one, for example to point at
+    /// where a missing `;` should go
+    pub fn shrink_to_hi(&self) -> Self {
+        Self::new(self.source, self.hi, self.hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combining() {
+        let first = Span::new(0, 2, 5);
+        let last = Span::new(0, 9, 12);
+
+        assert_eq!(first.to(last), Span::new(0, 2, 12));
+        assert_eq!(last.to(first), Span::new(0, 2, 12));
+        assert_eq!(first.between(last), Span::new(0, 5, 9));
+        assert_eq!(first.shrink_to_lo(), Span::new(0, 2, 2));
+        assert!(first.shrink_to_hi().is_empty());
+    }
+
+    #[test]
+    fn char_range() {
+        // `é` is two bytes
+        let text = "s = \"é\";";
+        let literal = Span::new(0, 4, 8);
+
+        assert_eq!(&text[literal.lo..literal.hi], "\"é\"");
+        assert_eq!(literal.char_range(text), (4, 7));
+        assert_eq!(Span::new(0, 8, 9).char_range(text), (7, 8));
+    }
+
+    #[test]
+    fn char_columns() {
+        // The first line has a two byte `é`, so the second starts at byte 9, and
+        // has its own two byte `ü` before the span
+        let text = "a = 'é'\nb = \"ü\" + c;\nd;";
+        let line_start = 9;
+        let line = text[line_start..].lines().next().unwrap();
+        let c = Span::new(0, 20, 21);
+
+        assert_eq!(&text[c.lo..c.hi], "c");
+        assert_eq!(c.char_columns(line, line_start), (10, 11));
+
+        // A span running onto the next line is cut off at the end of this one
+        let rest = Span::new(0, 20, 25);
+
+        assert_eq!(rest.char_columns(line, line_start), (10, 12));
+    }
+}
@@ -0,0 +1,47 @@
+//! Properties of the machine being compiled for that the front end needs to
+//! know about, such as the widths of the C integer types.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    AArch64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetInfo {
+    pub arch: Arch,
+    /// Size of a pointer in bytes
+    pub pointer_size: u8,
+    /// Size of `long` in bytes, which is what distinguishes LP64 from ILP32
+    pub long_size: u8,
+    /// Whether plain `char` is signed
+    pub char_signed: bool,
+}
+
+impl TargetInfo {
+    /// x86_64 following the System V ABI, as used by Linux and the BSDs
+    pub fn x86_64_sysv() -> Self {
+        Self {
+            arch: Arch::X86_64,
+            pointer_size: 8,
+            long_size: 8,
+            char_signed: true,
+        }
+    }
+
+    /// AArch64 following AAPCS64, where plain `char` is unsigned
+    pub fn aarch64_linux() -> Self {
+        Self {
+            arch: Arch::AArch64,
+            pointer_size: 8,
+            long_size: 8,
+            char_signed: false,
+        }
+    }
+}
+
+impl Default for TargetInfo {
+    fn default() -> Self {
+        Self::x86_64_sysv()
+    }
+}
@@ -0,0 +1,634 @@
+//! The C type system.
+//!
+//! Types are interned in a [`TypeContext`], and the [`Type`] handle that the
+//! rest of the compiler passes around is just an index plus the qualifiers that
+//! apply at the outermost level, so comparing two types for identity is a
+//! simple `==`. Qualifiers on nested types live on the nested handle, so
+//! `const int *` is a pointer (with no qualifiers) to a `const int`.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::target::{Arch, TargetInfo};
+
+pub mod layout;
+
+use layout::RecordLayout;
+
+/// Type qualifiers (C11 6.7.3)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+}
+
+impl Qualifiers {
+    pub const NONE: Self = Self {
+        is_const: false,
+        is_volatile: false,
+        is_restrict: false,
+    };
+
+    pub const CONST: Self = Self {
+        is_const: true,
+        is_volatile: false,
+        is_restrict: false,
+    };
+
+    pub const VOLATILE: Self = Self {
+        is_const: false,
+        is_volatile: true,
+        is_restrict: false,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+        }
+    }
+
+    /// Whether every qualifier in `other` is also present in `self`
+    pub fn contains(self, other: Self) -> bool {
+        self.union(other) == self
+    }
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+        ];
+
+        let mut first = true;
+
+        for (_, name) in names.iter().filter(|(present, _)| *present) {
+            if !first {
+                f.write_char(' ')?;
+            }
+
+            f.write_str(name)?;
+            first = false;
+        }
+
+        Ok(())
+    }
+}
+
+/// The standard integer types. Plain `char` is distinct from both `signed char`
+/// and `unsigned char` even though it behaves like one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntKind {
+    Bool,
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+}
+
+impl IntKind {
+    /// The integer conversion rank (C11 6.3.1.1), where a higher rank wins
+    pub fn rank(self) -> u8 {
+        match self {
+            Self::Bool => 0,
+            Self::Char | Self::SChar | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt => 3,
+            Self::Long | Self::ULong => 4,
+            Self::LongLong | Self::ULongLong => 5,
+        }
+    }
+
+    pub fn is_signed(self, target: &TargetInfo) -> bool {
+        match self {
+            Self::Char => target.char_signed,
+            Self::SChar | Self::Short | Self::Int | Self::Long | Self::LongLong => true,
+            Self::Bool
+            | Self::UChar
+            | Self::UShort
+            | Self::UInt
+            | Self::ULong
+            | Self::ULongLong => false,
+        }
+    }
+
+    /// The size of the type in bytes
+    pub fn size(self, target: &TargetInfo) -> u64 {
+        match self {
+            Self::Bool | Self::Char | Self::SChar | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt => 4,
+            Self::Long | Self::ULong => target.long_size as u64,
+            Self::LongLong | Self::ULongLong => 8,
+        }
+    }
+
+    /// The width of the type in bits, not counting padding
+    pub fn width(self, target: &TargetInfo) -> u32 {
+        match self {
+            Self::Bool => 1,
+            _ => self.size(target) as u32 * 8,
+        }
+    }
+
+    /// The smallest and largest values the type can represent
+    pub fn range(self, target: &TargetInfo) -> (i128, i128) {
+        let width = self.width(target);
+
+        if self.is_signed(target) {
+            (-(1 << (width - 1)), (1 << (width - 1)) - 1)
+        } else {
+            (0, (1 << width) - 1)
+        }
+    }
+
+    pub fn fits(self, value: i128, target: &TargetInfo) -> bool {
+        let (min, max) = self.range(target);
+        (min..=max).contains(&value)
+    }
+
+    /// Converts a value to this type the way a two's complement machine does,
+    /// keeping only the low bits and reinterpreting them according to the
+    /// signedness of the type. `_Bool` instead compares against zero.
+    pub fn wrap(self, value: i128, target: &TargetInfo) -> i128 {
+        if self == Self::Bool {
+            return (value != 0) as i128;
+        }
+
+        let width = self.width(target);
+        let mask = (1i128 << width) - 1;
+        let low = value & mask;
+
+        if self.is_signed(target) && low >> (width - 1) != 0 {
+            low - (1 << width)
+        } else {
+            low
+        }
+    }
+
+    /// The unsigned type with the same rank, used by the usual arithmetic
+    /// conversions when a signed type can't represent an unsigned one
+    pub fn to_unsigned(self) -> Self {
+        match self {
+            Self::Char | Self::SChar => Self::UChar,
+            Self::Short => Self::UShort,
+            Self::Int => Self::UInt,
+            Self::Long => Self::ULong,
+            Self::LongLong => Self::ULongLong,
+            other => other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Bool => "_Bool",
+            Self::Char => "char",
+            Self::SChar => "signed char",
+            Self::UChar => "unsigned char",
+            Self::Short => "short",
+            Self::UShort => "unsigned short",
+            Self::Int => "int",
+            Self::UInt => "unsigned int",
+            Self::Long => "long",
+            Self::ULong => "unsigned long",
+            Self::LongLong => "long long",
+            Self::ULongLong => "unsigned long long",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatKind {
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl FloatKind {
+    /// The number of significand bits, including the implicit leading one,
+    /// which is the widest integer the type represents exactly
+    pub fn precision(self, target: &TargetInfo) -> u32 {
+        match self {
+            Self::Float => 24,
+            Self::Double => 53,
+            Self::LongDouble => match target.arch {
+                Arch::X86_64 => 64,
+                Arch::AArch64 => 113,
+            },
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Float => "float",
+            Self::Double => "double",
+            Self::LongDouble => "long double",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordKind {
+    Struct,
+    Union,
+}
+
+/// Identifies a struct or union declaration. Records are nominal, so two
+/// structs with identical members are still different types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordId(u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// `None` for unnamed members such as anonymous structs and padding
+    /// bit-fields
+    pub name: Option<String>,
+    pub ty: Type,
+    /// The declared width in bits if this is a bit-field
+    pub bit_width: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordDef {
+    pub kind: RecordKind,
+    /// `None` for anonymous records
+    pub tag: Option<String>,
+    /// `None` until the record is defined, while it is still incomplete
+    pub members: Option<Vec<Member>>,
+    /// Computed as soon as the record is defined
+    pub layout: Option<RecordLayout>,
+}
+
+/// The structure of a type, without any outermost qualifiers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Void,
+    Integer(IntKind),
+    Floating(FloatKind),
+    Pointer(Type),
+    Array {
+        element: Type,
+        /// `None` for arrays of unknown size, like `int a[]`
+        len: Option<u64>,
+    },
+    Function {
+        ret: Type,
+        params: Vec<Type>,
+        variadic: bool,
+        /// Whether the parameters are known, which is false for old-style
+        /// declarations like `int f()`
+        prototyped: bool,
+    },
+    Record(RecordKind, RecordId),
+}
+
+/// A handle to an interned type along with its outermost qualifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Type {
+    index: u32,
+    pub quals: Qualifiers,
+}
+
+impl Type {
+    pub fn with_quals(self, quals: Qualifiers) -> Self {
+        Self {
+            index: self.index,
+            quals: self.quals.union(quals),
+        }
+    }
+
+    /// The same type without any outermost qualifiers
+    pub fn unqualified(self) -> Self {
+        Self {
+            index: self.index,
+            quals: Qualifiers::NONE,
+        }
+    }
+}
+
+/// Owns every type created while compiling a translation unit
+#[derive(Debug)]
+pub struct TypeContext {
+    kinds: Vec<TypeKind>,
+    interned: HashMap<TypeKind, u32>,
+    records: Vec<RecordDef>,
+    target: TargetInfo,
+}
+
+impl TypeContext {
+    pub fn new(target: TargetInfo) -> Self {
+        Self {
+            kinds: Vec::new(),
+            interned: HashMap::new(),
+            records: Vec::new(),
+            target,
+        }
+    }
+
+    pub fn target(&self) -> &TargetInfo {
+        &self.target
+    }
+
+    /// Returns the unqualified type with the given structure, creating it if it
+    /// doesn't exist yet
+    pub fn intern(&mut self, kind: TypeKind) -> Type {
+        let index = match self.interned.get(&kind) {
+            Some(&index) => index,
+            None => {
+                let index = self.kinds.len() as u32;
+                self.kinds.push(kind.clone());
+                self.interned.insert(kind, index);
+                index
+            }
+        };
+
+        Type {
+            index,
+            quals: Qualifiers::NONE,
+        }
+    }
+
+    pub fn kind(&self, ty: Type) -> &TypeKind {
+        &self.kinds[ty.index as usize]
+    }
+
+    pub fn void(&mut self) -> Type {
+        self.intern(TypeKind::Void)
+    }
+
+    pub fn int(&mut self, kind: IntKind) -> Type {
+        self.intern(TypeKind::Integer(kind))
+    }
+
+    pub fn float(&mut self, kind: FloatKind) -> Type {
+        self.intern(TypeKind::Floating(kind))
+    }
+
+    pub fn pointer_to(&mut self, pointee: Type) -> Type {
+        self.intern(TypeKind::Pointer(pointee))
+    }
+
+    pub fn array_of(&mut self, element: Type, len: Option<u64>) -> Type {
+        self.intern(TypeKind::Array { element, len })
+    }
+
+    pub fn function(&mut self, ret: Type, params: Vec<Type>, variadic: bool) -> Type {
+        self.intern(TypeKind::Function {
+            ret,
+            params,
+            variadic,
+            prototyped: true,
+        })
+    }
+
+    /// Creates a new, incomplete struct or union type, along with the id to
+    /// define it through. Unlike every other type this is never deduplicated,
+    /// since each declaration is a distinct type.
+    pub fn new_record(&mut self, kind: RecordKind, tag: Option<&str>) -> (Type, RecordId) {
+        let id = RecordId(self.records.len() as u32);
+
+        self.records.push(RecordDef {
+            kind,
+            tag: tag.map(str::to_owned),
+            members: None,
+            layout: None,
+        });
+
+        (self.intern(TypeKind::Record(kind, id)), id)
+    }
+
+    /// Completes a record created with [`new_record`](Self::new_record) and
+    /// lays it out. Every member must already have a complete type, apart from
+    /// a flexible array member at the end of a struct.
+    pub fn define_record(&mut self, id: RecordId, members: Vec<Member>) {
+        debug_assert!(self.record(id).members.is_none(), "record defined twice");
+
+        let layout = self.compute_layout(self.record(id).kind, &members);
+        let record = &mut self.records[id.0 as usize];
+
+        record.members = Some(members);
+        record.layout = Some(layout);
+    }
+
+    pub fn record(&self, id: RecordId) -> &RecordDef {
+        &self.records[id.0 as usize]
+    }
+
+    pub fn is_void(&self, ty: Type) -> bool {
+        matches!(self.kind(ty), TypeKind::Void)
+    }
+
+    pub fn is_integer(&self, ty: Type) -> bool {
+        matches!(self.kind(ty), TypeKind::Integer(_))
+    }
+
+    pub fn is_floating(&self, ty: Type) -> bool {
+        matches!(self.kind(ty), TypeKind::Floating(_))
+    }
+
+    pub fn is_arithmetic(&self, ty: Type) -> bool {
+        self.is_integer(ty) || self.is_floating(ty)
+    }
+
+    pub fn is_pointer(&self, ty: Type) -> bool {
+        matches!(self.kind(ty), TypeKind::Pointer(_))
+    }
+
+    pub fn is_scalar(&self, ty: Type) -> bool {
+        self.is_arithmetic(ty) || self.is_pointer(ty)
+    }
+
+    /// Whether the type is an integer type that is signed on this target
+    pub fn is_signed(&self, ty: Type) -> bool {
+        match self.kind(ty) {
+            TypeKind::Integer(kind) => kind.is_signed(&self.target),
+            _ => false,
+        }
+    }
+
+    /// Whether the size of objects of this type is known (C11 6.2.5p1)
+    pub fn is_complete(&self, ty: Type) -> bool {
+        match self.kind(ty) {
+            TypeKind::Void | TypeKind::Function { .. } => false,
+            TypeKind::Array { len, .. } => len.is_some(),
+            TypeKind::Record(_, id) => self.record(*id).members.is_some(),
+            _ => true,
+        }
+    }
+
+    /// Formats a type the way it would be written in C, e.g. `int (*)[4]`
+    pub fn display(&self, ty: Type) -> String {
+        self.declarator(ty, String::new())
+    }
+
+    /// Formats `ty` as the type of a declaration whose declarator so far is
+    /// `inner`, working outwards from the name the way C declarators are read
+    fn declarator(&self, ty: Type, inner: String) -> String {
+        let quals = ty.quals;
+
+        match self.kind(ty) {
+            TypeKind::Pointer(pointee) => {
+                let mut star = String::from("*");
+
+                if !quals.is_empty() {
+                    write!(star, " {}", quals).unwrap();
+
+                    if !inner.is_empty() {
+                        star.push(' ');
+                    }
+                }
+
+                star.push_str(&inner);
+
+                let needs_parens = matches!(
+                    self.kind(*pointee),
+                    TypeKind::Array { .. } | TypeKind::Function { .. }
+                );
+
+                if needs_parens {
+                    star = format!("({})", star);
+                }
+
+                self.declarator(*pointee, star)
+            }
+            TypeKind::Array { element, len } => {
+                let suffix = match len {
+                    Some(len) => format!("{}[{}]", inner, len),
+                    None => format!("{}[]", inner),
+                };
+
+                self.declarator(*element, suffix)
+            }
+            TypeKind::Function {
+                ret,
+                params,
+                variadic,
+                prototyped,
+            } => {
+                let mut list: Vec<_> = params.iter().map(|p| self.display(*p)).collect();
+
+                if *variadic {
+                    list.push(String::from("..."));
+                }
+
+                if list.is_empty() && *prototyped {
+                    list.push(String::from("void"));
+                }
+
+                self.declarator(*ret, format!("{}({})", inner, list.join(", ")))
+            }
+            base => {
+                let mut out = String::new();
+
+                if !quals.is_empty() {
+                    write!(out, "{} ", quals).unwrap();
+                }
+
+                match base {
+                    TypeKind::Void => out.push_str("void"),
+                    TypeKind::Integer(kind) => out.push_str(kind.name()),
+                    TypeKind::Floating(kind) => out.push_str(kind.name()),
+                    TypeKind::Record(kind, id) => {
+                        out.push_str(match kind {
+                            RecordKind::Struct => "struct ",
+                            RecordKind::Union => "union ",
+                        });
+
+                        match &self.record(*id).tag {
+                            Some(tag) => out.push_str(tag),
+                            None => out.push_str("<anonymous>"),
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+
+                if !inner.is_empty() {
+                    out.push(' ');
+                    out.push_str(&inner);
+                }
+
+                out
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let a = ctx.pointer_to(int);
+        let b = ctx.pointer_to(int);
+        let c = ctx.pointer_to(int.with_quals(Qualifiers::CONST));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(int.with_quals(Qualifiers::CONST).unqualified(), int);
+    }
+
+    #[test]
+    fn records_are_nominal() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let (a, id) = ctx.new_record(RecordKind::Struct, Some("s"));
+        let (b, _) = ctx.new_record(RecordKind::Struct, Some("s"));
+
+        assert_ne!(a, b);
+        assert!(!ctx.is_complete(a));
+
+        let int = ctx.int(IntKind::Int);
+
+        ctx.define_record(
+            id,
+            vec![Member {
+                name: Some(String::from("x")),
+                ty: int,
+                bit_width: None,
+            }],
+        );
+
+        assert!(ctx.is_complete(a));
+    }
+
+    #[test]
+    fn display() {
+        let mut ctx = TypeContext::new(TargetInfo::default());
+
+        let int = ctx.int(IntKind::Int);
+        let char = ctx.int(IntKind::Char);
+        let const_char_ptr = ctx.pointer_to(char.with_quals(Qualifiers::CONST));
+        let array = ctx.array_of(int, Some(4));
+        let array_ptr = ctx.pointer_to(array);
+        let func = ctx.function(int, vec![const_char_ptr], true);
+        let func_ptr = ctx.pointer_to(func);
+        let const_ptr = ctx.pointer_to(int).with_quals(Qualifiers::CONST);
+        let void = ctx.void();
+        let no_params = ctx.function(void, vec![], false);
+
+        assert_eq!(ctx.display(const_char_ptr), "const char *");
+        assert_eq!(ctx.display(array_ptr), "int (*)[4]");
+        assert_eq!(ctx.display(func_ptr), "int (*)(const char *, ...)");
+        assert_eq!(ctx.display(const_ptr), "int * const");
+        assert_eq!(ctx.display(no_params), "void (void)");
+    }
+}
@@ -0,0 +1,414 @@
+//! Sizes, alignments and struct/union member offsets.
+//!
+//! Both supported targets follow the same rules here: every scalar is aligned
+//! to its own size, a struct places each member at the next offset that is a
+//! multiple of its alignment, and the size of a record is rounded up to the
+//! largest alignment of its members so that arrays of it stay aligned.
+//!
+//! Bit-fields follow the System V rules, which AAPCS64 shares: a bit-field is
+//! packed right after the previous one unless that would make it straddle a
+//! naturally aligned unit of its declared type, in which case it starts at the
+//! next such unit. A zero-width bit-field pads to the next unit of its type,
+//! and unnamed bit-fields don't affect the alignment of the record.
+
+use super::{FloatKind, Member, RecordId, RecordKind, Type, TypeContext, TypeKind};
+
+/// Where each member of a record lives, in the same order as the members
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordLayout {
+    pub size: u64,
+    pub align: u64,
+    pub fields: Vec<FieldLayout>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLayout {
+    /// The byte offset of the member from the start of the record. For a
+    /// bit-field, this is the offset of the storage unit holding it, which is
+    /// as large as its declared type and naturally aligned.
+    pub offset: u64,
+    pub bitfield: Option<BitfieldLayout>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitfieldLayout {
+    /// The offset in bits from the least significant bit of the storage unit
+    pub bit_offset: u32,
+    pub width: u32,
+}
+
+/// Rounds `offset` up to the next multiple of `align`, which must be a power
+/// of two
+pub fn align_to(offset: u64, align: u64) -> u64 {
+    debug_assert!(align.is_power_of_two());
+    (offset + align - 1) & !(align - 1)
+}
+
+impl FloatKind {
+    /// The size of the type in bytes
+    pub fn size(self) -> u64 {
+        match self {
+            Self::Float => 4,
+            Self::Double => 8,
+            // x87 extended precision padded out on x86_64, and IEEE quad
+            // precision on AArch64, which happen to take the same space
+            Self::LongDouble => 16,
+        }
+    }
+}
+
+impl TypeContext {
+    /// The size of a type in bytes, which is `None` for incomplete types and
+    /// functions, to which `sizeof` can't be applied
+    pub fn size_of(&self, ty: Type) -> Option<u64> {
+        match self.kind(ty) {
+            TypeKind::Void | TypeKind::Function { .. } => None,
+            TypeKind::Integer(kind) => Some(kind.size(self.target())),
+            TypeKind::Floating(kind) => Some(kind.size()),
+            TypeKind::Pointer(_) => Some(self.target().pointer_size as u64),
+            TypeKind::Array { element, len } => {
+                let element = self.size_of(*element)?;
+                len.map(|len| element * len)
+            }
+            TypeKind::Record(_, id) => Some(self.record_layout(*id)?.size),
+        }
+    }
+
+    /// The required alignment of a type in bytes, which is `None` whenever
+    /// [`size_of`](Self::size_of) is, apart from arrays of unknown size
+    pub fn align_of(&self, ty: Type) -> Option<u64> {
+        match self.kind(ty) {
+            TypeKind::Array { element, .. } => self.align_of(*element),
+            TypeKind::Record(_, id) => Some(self.record_layout(*id)?.align),
+            _ => self.size_of(ty),
+        }
+    }
+
+    /// The layout of a record, or `None` if it hasn't been defined yet
+    pub fn record_layout(&self, id: RecordId) -> Option<&RecordLayout> {
+        self.record(id).layout.as_ref()
+    }
+
+    /// Finds a member by name, looking through anonymous structs and unions,
+    /// and returns its type and where it lives relative to the start of the
+    /// record. A member found through an anonymous member also gets that
+    /// member's qualifiers, just as if it had been accessed through it by name.
+    pub fn lookup_member(&self, id: RecordId, name: &str) -> Option<(FieldLayout, Type)> {
+        let record = self.record(id);
+        let members = record.members.as_ref()?;
+        let layout = record.layout.as_ref()?;
+
+        for (member, field) in members.iter().zip(&layout.fields) {
+            match &member.name {
+                Some(member_name) if member_name == name => return Some((*field, member.ty)),
+                Some(_) => {}
+                None if member.bit_width.is_some() => {}
+                None => {
+                    if let TypeKind::Record(_, inner) = self.kind(member.ty) {
+                        if let Some((mut inner, ty)) = self.lookup_member(*inner, name) {
+                            inner.offset += field.offset;
+                            return Some((inner, ty.with_quals(member.ty.quals)));
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    pub(super) fn compute_layout(&self, kind: RecordKind, members: &[Member]) -> RecordLayout {
+        let mut fields = Vec::with_capacity(members.len());
+        // Tracked in bits so that bit-fields can share bytes
+        let mut size_bits = 0;
+        let mut align = 1;
+
+        for (i, member) in members.iter().enumerate() {
+            let member_align = self.align_of(member.ty).expect("member of incomplete type");
+
+            if let Some(width) = member.bit_width {
+                let unit_size = self
+                    .size_of(member.ty)
+                    .expect("bit-field of incomplete type");
+
+                let (field, end) = place_bitfield(kind, size_bits, width, unit_size, member_align);
+
+                fields.push(field);
+                size_bits = size_bits.max(end);
+
+                if member.name.is_some() {
+                    align = align.max(member_align);
+                }
+
+                continue;
+            }
+
+            // A flexible array member takes up no space of its own
+            let member_size = match self.size_of(member.ty) {
+                Some(size) => size,
+                None => {
+                    debug_assert!(
+                        kind == RecordKind::Struct && i == members.len() - 1,
+                        "member of incomplete type"
+                    );
+                    0
+                }
+            };
+
+            align = align.max(member_align);
+
+            let offset = match kind {
+                RecordKind::Struct => align_to(bits_to_bytes(size_bits), member_align),
+                RecordKind::Union => 0,
+            };
+
+            fields.push(FieldLayout {
+                offset,
+                bitfield: None,
+            });
+            size_bits = size_bits.max((offset + member_size) * 8);
+        }
+
+        RecordLayout {
+            size: align_to(bits_to_bytes(size_bits), align),
+            align,
+            fields,
+        }
+    }
+}
+
+fn bits_to_bytes(bits: u64) -> u64 {
+    bits.div_ceil(8)
+}
+
+/// Places a bit-field of `width` bits whose declared type is `unit_size` bytes
+/// large, with the previous members taking up `size_bits` bits. Returns its
+/// layout and the bit just past it.
+fn place_bitfield(
+    kind: RecordKind,
+    size_bits: u64,
+    width: u32,
+    unit_size: u64,
+    unit_align: u64,
+) -> (FieldLayout, u64) {
+    let width_bits = width as u64;
+    let unit_bits = unit_size * 8;
+    let align_bits = unit_align * 8;
+
+    let start = match kind {
+        RecordKind::Union => 0,
+        // Pads to the next unit, but doesn't occupy any of it
+        RecordKind::Struct if width == 0 => align_to(size_bits, align_bits),
+        RecordKind::Struct => {
+            let straddles = size_bits / align_bits != (size_bits + width_bits - 1) / align_bits;
+
+            if straddles {
+                align_to(size_bits, align_bits)
+            } else {
+                size_bits
+            }
+        }
+    };
+
+    let unit_start = start / unit_bits * unit_bits;
+
+    let field = FieldLayout {
+        offset: unit_start / 8,
+        bitfield: Some(BitfieldLayout {
+            bit_offset: (start - unit_start) as u32,
+            width,
+        }),
+    };
+
+    (field, start + width_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::TargetInfo;
+    use crate::types::IntKind;
+
+    fn member(name: &str, ty: Type) -> Member {
+        Member {
+            name: Some(name.to_owned()),
+            ty,
+            bit_width: None,
+        }
+    }
+
+    fn bitfield(name: Option<&str>, ty: Type, width: u32) -> Member {
+        Member {
+            name: name.map(str::to_owned),
+            ty,
+            bit_width: Some(width),
+        }
+    }
+
+    fn define(ctx: &mut TypeContext, kind: RecordKind, members: Vec<Member>) -> (Type, RecordId) {
+        let (ty, id) = ctx.new_record(kind, None);
+
+        ctx.define_record(id, members);
+
+        (ty, id)
+    }
+
+    fn offsets(ctx: &TypeContext, id: RecordId) -> Vec<(u64, Option<u32>)> {
+        ctx.record_layout(id)
+            .unwrap()
+            .fields
+            .iter()
+            .map(|f| (f.offset, f.bitfield.map(|b| b.bit_offset)))
+            .collect()
+    }
+
+    #[test]
+    fn struct_padding() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let char = ctx.int(IntKind::Char);
+        let int = ctx.int(IntKind::Int);
+        let long = ctx.int(IntKind::Long);
+        let (s, id) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![member("a", char), member("b", long), member("c", int)],
+        );
+
+        assert_eq!(offsets(&ctx, id), [(0, None), (8, None), (16, None)]);
+        assert_eq!(ctx.size_of(s), Some(24));
+        assert_eq!(ctx.align_of(s), Some(8));
+    }
+
+    #[test]
+    fn unions_and_arrays() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let char = ctx.int(IntKind::Char);
+        let short = ctx.int(IntKind::Short);
+        let chars = ctx.array_of(char, Some(5));
+        let (u, _) = define(
+            &mut ctx,
+            RecordKind::Union,
+            vec![member("a", chars), member("b", short)],
+        );
+
+        let array = ctx.array_of(u, Some(3));
+
+        assert_eq!(ctx.size_of(u), Some(6));
+        assert_eq!(ctx.align_of(u), Some(2));
+        assert_eq!(ctx.size_of(array), Some(18));
+    }
+
+    #[test]
+    fn anonymous_members_and_flexible_arrays() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let int = ctx.int(IntKind::Int);
+        let double = ctx.float(FloatKind::Double);
+        let flexible = ctx.array_of(int, None);
+
+        let (inner, _) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![member("x", int), member("y", double)],
+        );
+
+        let (outer, id) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![
+                member("tag", int),
+                Member {
+                    name: None,
+                    ty: inner,
+                    bit_width: None,
+                },
+                member("data", flexible),
+            ],
+        );
+
+        let offset = |name| ctx.lookup_member(id, name).map(|(f, ty)| (f.offset, ty));
+
+        assert_eq!(offset("y"), Some((16, double)));
+        assert_eq!(offset("data"), Some((24, flexible)));
+        assert_eq!(offset("z"), None);
+        assert_eq!(ctx.size_of(outer), Some(24));
+    }
+
+    #[test]
+    fn packed_flags() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let uint = ctx.int(IntKind::UInt);
+        let (s, id) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![
+                bitfield(Some("a"), uint, 1),
+                bitfield(Some("b"), uint, 3),
+                bitfield(Some("c"), uint, 28),
+            ],
+        );
+
+        assert_eq!(
+            offsets(&ctx, id),
+            [(0, Some(0)), (0, Some(1)), (0, Some(4))]
+        );
+        assert_eq!(ctx.size_of(s), Some(4));
+        assert_eq!(ctx.align_of(s), Some(4));
+    }
+
+    #[test]
+    fn straddling_bitfields_move_to_next_unit() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let char = ctx.int(IntKind::Char);
+        let int = ctx.int(IntKind::Int);
+        let (s, id) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![
+                bitfield(Some("a"), char, 3),
+                bitfield(Some("b"), char, 6),
+                bitfield(Some("c"), int, 12),
+                bitfield(Some("d"), int, 20),
+            ],
+        );
+
+        // `c` fits in the first int after `b`, `d` would cross into the next
+        assert_eq!(
+            offsets(&ctx, id),
+            [(0, Some(0)), (1, Some(0)), (0, Some(14)), (4, Some(0))]
+        );
+        assert_eq!(ctx.size_of(s), Some(8));
+    }
+
+    #[test]
+    fn zero_width_and_unnamed_bitfields() {
+        let mut ctx = TypeContext::new(TargetInfo::x86_64_sysv());
+
+        let char = ctx.int(IntKind::Char);
+        let int = ctx.int(IntKind::Int);
+        let (s, id) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![member("c", char), bitfield(None, int, 0), member("d", char)],
+        );
+
+        // The zero-width bit-field pads to the next int without aligning the
+        // struct to one
+        assert_eq!(offsets(&ctx, id)[2], (4, None));
+        assert_eq!(ctx.size_of(s), Some(5));
+        assert_eq!(ctx.align_of(s), Some(1));
+
+        let (s, _) = define(
+            &mut ctx,
+            RecordKind::Struct,
+            vec![bitfield(Some("a"), char, 4), bitfield(None, int, 12)],
+        );
+
+        assert_eq!(ctx.size_of(s), Some(2));
+        assert_eq!(ctx.align_of(s), Some(1));
+    }
+}